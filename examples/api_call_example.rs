@@ -1,6 +1,3 @@
-use std::net::SocketAddr;
-
-use anyhow::{Result, anyhow};
 use axum::body::Body;
 use axum::extract::State;
 use axum::http::Request;
@@ -10,141 +7,94 @@ use axum::{Router, debug_handler};
 use html_escape::encode_text;
 use zoraxy_rs::prelude::*;
 
-fn introspect() -> IntroSpect {
-    let metadata = PluginMetadata::new(PluginType::Utilities)
-        .with_id("org.aroz.zoraxy.api-call-example")
-        .with_name("API Call Example Plugin")
-        .with_author("Anthony Rubick")
-        .with_description("An example plugin for making API calls")
-        .with_url("https://zoraxy.aroz.org")
-        .with_version((1, 0, 0));
-    IntroSpect::new(metadata)
-        .with_ui_path("/ui")
-        .add_permitted_api_endpoint(
-            PermittedApiEndpoint::new("GET", "/plugin/api/access/list")
-                .with_reason("Used to display all configured Access Rules"),
-        )
+struct ApiCallExample {
+    context: Context,
+}
+
+impl ZoraxyPlugin for ApiCallExample {
+    fn introspect() -> IntroSpect {
+        let metadata = PluginMetadata::new(PluginType::Utilities)
+            .with_id("org.aroz.zoraxy.api-call-example")
+            .with_name("API Call Example Plugin")
+            .with_author("Anthony Rubick")
+            .with_description("An example plugin for making API calls")
+            .with_url("https://zoraxy.aroz.org")
+            .with_version((1, 0, 0));
+        IntroSpect::new(metadata)
+            .with_ui_path("/ui")
+            .add_permitted_api_endpoint(
+                PermittedApiEndpoint::new("GET", "/plugin/api/access/list")
+                    .with_reason("Used to display all configured Access Rules"),
+            )
+    }
+
+    fn configure(spec: &ConfigureSpec) -> anyhow::Result<Self> {
+        let client = ZoraxyClient::from_spec(spec)?;
+        tracing::info!(
+            "API Call Example Plugin initialized with port: {}, zoraxy: {}",
+            spec.port,
+            client.base_url()
+        );
+
+        Ok(Self {
+            context: Context {
+                port: spec.port,
+                client,
+            },
+        })
+    }
+
+    fn build_router(&self, _spec: &ConfigureSpec) -> anyhow::Result<Router> {
+        Ok(Router::new().fallback(get(async |_: Request<Body>| Html("<h1>Not Found</h1>"))))
+    }
+
+    fn build_ui_router(&self, _spec: &ConfigureSpec) -> anyhow::Result<Option<Router>> {
+        Ok(Some(
+            Router::new()
+                .route("/", get(render_ui))
+                .with_state(self.context.clone()),
+        ))
+    }
+
+    fn debug_logging(_spec: &ConfigureSpec) -> bool {
+        false
+    }
 }
 
 #[derive(Clone, Debug)]
 struct Context {
     port: u16,
-    api_key: String,
-    zoraxy_port: u16,
+    client: ZoraxyClient,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let runtime_cfg = serve_and_recv_spec(std::env::args().collect(), &introspect())?;
-
-    init_tracing_subscriber(false);
-
-    let api_key = runtime_cfg
-        .api_key
-        .clone()
-        .ok_or(anyhow!("missing API Key in runtime configuration"))?;
-    let zoraxy_port = runtime_cfg
-        .zoraxy_port
-        .ok_or(anyhow!("missing Zoraxy Port in runtime configuration"))?;
-    tracing::info!(
-        "API Call Example Plugin initialized with port: {}, api_key: {}, zoraxy_port: {}",
-        runtime_cfg.port,
-        api_key,
-        zoraxy_port
-    );
-
-    let state = Context {
-        port: runtime_cfg.port,
-        api_key,
-        zoraxy_port,
-    };
-
-    let app = Router::new()
-        .route("/ui/", get(render_ui))
-        .fallback(get(async |_: Request<Body>| Html("<h1>Not Found</h1>")));
-    let addr: SocketAddr = format!("127.0.0.1:{}", runtime_cfg.port).parse()?;
-    tracing::info!("API Call Example Plugin UI ready at http://{addr}");
-    start_plugin(app, state, addr, Some("/ui")).await
+    run::<ApiCallExample>().await
 }
 
-async fn allowed_endpoint(ctx: &Context) -> Result<String> {
+async fn allowed_endpoint(ctx: &Context) -> Result<String, ZoraxyApiError> {
     // Make an API call to the permitted endpoint
-    let client = reqwest::Client::new();
-    let api_url = format!(
-        "http://localhost:{}/plugin/api/access/list",
-        ctx.zoraxy_port
-    );
-    let resp = client
-        .get(&api_url)
-        .bearer_auth(&ctx.api_key)
-        .send()
-        .await?;
-    tracing::info!("Allowed endpoint response status: {}", resp.status());
-    if resp.status().is_success() {
-        return Ok(resp.text().await?);
-    } else {
-        return Err(anyhow!(resp.text().await?));
-    }
+    let rules = ctx.client.access_list().await?;
+    tracing::info!("Allowed endpoint returned {} access rules", rules.len());
+    Ok(serde_json::to_string_pretty(&rules).unwrap_or_default())
 }
 
-async fn allowed_endpoint_invalid_key(ctx: &Context) -> Result<String> {
+async fn allowed_endpoint_invalid_key(ctx: &Context) -> Result<String, ZoraxyApiError> {
     // Make an API call to the permitted endpoint with an invalid key
-    let client = reqwest::Client::new();
-    let api_url = format!(
-        "http://localhost:{}/plugin/api/access/list",
-        ctx.zoraxy_port
-    );
-    let resp = client
-        .get(&api_url)
-        .bearer_auth("invalid-key")
-        .send()
-        .await?;
-    tracing::info!(
-        "Allowed endpoint invalid key response status: {}",
-        resp.status()
-    );
-    if resp.status().is_success() {
-        return Ok(resp.text().await?);
-    } else {
-        return Err(anyhow!(resp.text().await?));
-    }
+    let client = ctx.client.clone().with_api_key("invalid-key");
+    let rules = client.access_list().await?;
+    Ok(serde_json::to_string_pretty(&rules).unwrap_or_default())
 }
 
-async fn unaccessible_endpoint(ctx: &Context) -> Result<String> {
+async fn unaccessible_endpoint(ctx: &Context) -> Result<String, ZoraxyApiError> {
     // Make an API call to an endpoint that is not permitted
-    let client = reqwest::Client::new();
-    let api_url = format!(
-        "http://localhost:{}/api/acme/listExpiredDomains",
-        ctx.zoraxy_port
-    );
-    let resp = client
-        .get(&api_url)
-        .bearer_auth(&ctx.api_key)
-        .send()
-        .await?;
-    tracing::info!("Unaccessible endpoint response status: {}", resp.status());
-    if resp.status().is_success() {
-        return Ok(resp.text().await?);
-    } else {
-        return Err(anyhow!(resp.text().await?));
-    }
+    ctx.client.get_text("/api/acme/listExpiredDomains").await
 }
 
-async fn unpermitted_endpoint(ctx: &Context) -> Result<String> {
+async fn unpermitted_endpoint(ctx: &Context) -> Result<String, ZoraxyApiError> {
     // Make an API call to an endpoint that is plugin-accessible but is not permitted
-    let client = reqwest::Client::new();
-    let api_url = format!("http://localhost:{}/plugin/api/proxy/list", ctx.zoraxy_port);
-    let resp = client
-        .get(&api_url)
-        .bearer_auth(&ctx.api_key)
-        .send()
-        .await?;
-    tracing::info!("Unpermitted endpoint response status: {}", resp.status());
-    if resp.status().is_success() {
-        return Ok(resp.text().await?);
-    } else {
-        return Err(anyhow!(resp.text().await?));
-    }
+    let endpoints = ctx.client.proxy_list().await?;
+    Ok(serde_json::to_string_pretty(&endpoints).unwrap_or_default())
 }
 
 #[debug_handler]
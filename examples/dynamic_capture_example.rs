@@ -1,4 +1,5 @@
-use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
 
 use axum::{
     Router,
@@ -16,37 +17,74 @@ const SNIFF_INGRESS: &str = "/d_sniff";
 const SNIFF_INGRESS_SLASH: &str = "/d_sniff/";
 const CAPTURE_INGRESS: &str = "/d_capture";
 const CAPTURE_INGRESS_SLASH: &str = "/d_capture/";
-const UI_PATH: &str = "/debug";
-const UI_PATH_SLASH: &str = "/debug/";
-
-fn instrospect() -> IntroSpect {
-    let metadata = PluginMetadata::new(PluginType::Router)
-        .with_id("org.aroz.zoraxy.dynamic-capture-example")
-        .with_name("Zoraxy Dynamic Capture Example Plugin")
-        .with_author("aroz.org")
-        .with_contact("https://aroz.org")
-        .with_description("An example Zoraxy plugin demonstrating dynamic path capture routing.")
-        .with_url("https://zoraxy.aroz.org")
-        .with_version((1, 0, 0));
-    let settings = DynamicCaptureSettings::new(SNIFF_INGRESS, CAPTURE_INGRESS);
-    IntroSpect::new(metadata)
-        .with_dynamic_capture_settings(settings)
-        .with_ui_path(UI_PATH)
+
+struct DynamicCaptureExample {
+    /// Remote address of each accepted sniff, for the capture handler of the same request
+    sniffed_from: Arc<SniffStore<String>>,
+}
+
+impl ZoraxyPlugin for DynamicCaptureExample {
+    fn introspect() -> IntroSpect {
+        let metadata = PluginMetadata::new(PluginType::Router)
+            .with_id("org.aroz.zoraxy.dynamic-capture-example")
+            .with_name("Zoraxy Dynamic Capture Example Plugin")
+            .with_author("aroz.org")
+            .with_contact("https://aroz.org")
+            .with_description(
+                "An example Zoraxy plugin demonstrating dynamic path capture routing.",
+            )
+            .with_url("https://zoraxy.aroz.org")
+            .with_version((1, 0, 0));
+        let settings = DynamicCaptureSettings::new(SNIFF_INGRESS, CAPTURE_INGRESS);
+        IntroSpect::new(metadata)
+            .with_dynamic_capture_settings(settings)
+            .with_ui_path("/debug")
+    }
+
+    fn configure(_spec: &ConfigureSpec) -> anyhow::Result<Self> {
+        Ok(Self {
+            sniffed_from: Arc::new(SniffStore::new(Duration::from_secs(30))),
+        })
+    }
+
+    fn build_router(&self, _spec: &ConfigureSpec) -> anyhow::Result<Router> {
+        let capture_service =
+            DynamicCaptureService::new(CAPTURE_INGRESS_SLASH, capture.into_service())
+                .with_sniff_store(self.sniffed_from.clone());
+
+        Ok(Router::new()
+            .route(SNIFF_INGRESS_SLASH, post(sniff))
+            .with_state(self.sniffed_from.clone())
+            .nest_service(CAPTURE_INGRESS_SLASH, capture_service))
+    }
+
+    fn build_ui_router(&self, _spec: &ConfigureSpec) -> anyhow::Result<Option<Router>> {
+        Ok(Some(Router::new().route("/", get(render_debug_ui))))
+    }
+
+    fn debug_logging(_spec: &ConfigureSpec) -> bool {
+        true
+    }
 }
 
 #[debug_handler]
-async fn sniff(State(_): State<()>, sniff: DynamicSniffForwardRequest) -> SniffDecision {
+async fn sniff(
+    State(sniffed_from): State<Arc<SniffStore<String>>>,
+    sniff: DynamicSniffForwardRequest,
+) -> SniffDecision {
     if sniff.request_uri.starts_with("/foobar") {
         tracing::info!("Sniffed request: {:?}", sniff);
+        sniffed_from.attach(&sniff, sniff.remote_addr.clone());
 
         return SniffDecision::Accept;
     }
     SniffDecision::Skip
 }
 
-async fn capture(req: Request<Body>) -> impl IntoResponse {
+async fn capture(sniffed_from: Option<SniffData<String>>, req: Request<Body>) -> impl IntoResponse {
     Html(format!(
-        "<h1>Welcome to the dynamic capture handler!</h1><br/><h2>Request Info:</h2><p>Request URI: {}</p><p>Request Method: {}</p><p>Request Headers: {:#?}</p>",
+        "<h1>Welcome to the dynamic capture handler!</h1><br/><h2>Request Info:</h2><p>Sniffed from: {}</p><p>Request URI: {}</p><p>Request Method: {}</p><p>Request Headers: {:#?}</p>",
+        sniffed_from.map_or_else(|| "unknown".to_string(), |SniffData(addr)| addr),
         req.uri(),
         req.method(),
         req.headers()
@@ -55,20 +93,7 @@ async fn capture(req: Request<Body>) -> impl IntoResponse {
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let runtime_cfg = serve_and_recv_spec(std::env::args().collect(), &instrospect())?;
-
-    init_tracing_subscriber(true);
-
-    let capture_service = DynamicCaptureService::new("/d_capture/", capture.into_service());
-
-    let app = Router::new()
-        .route(SNIFF_INGRESS_SLASH, post(sniff))
-        .nest_service(CAPTURE_INGRESS_SLASH, capture_service)
-        .route(UI_PATH_SLASH, get(render_debug_ui));
-
-    let addr: SocketAddr = format!("127.0.0.1:{}", runtime_cfg.port).parse()?;
-    tracing::info!("Dynamic capture example listening on http://{addr}");
-    start_plugin(app, (), addr, Some(UI_PATH)).await
+    run::<DynamicCaptureExample>().await
 }
 
 async fn render_debug_ui(req: Request<Body>) -> Html<String> {
@@ -1,4 +1,4 @@
-use std::{net::SocketAddr, sync::Arc};
+use std::sync::Arc;
 
 use axum::{
     Json, Router, debug_handler,
@@ -9,37 +9,72 @@ use axum::{
 use tokio::sync::Mutex;
 use zoraxy_rs::prelude::*;
 
-fn instrospect() -> IntroSpect {
-    let metadata = PluginMetadata::new(PluginType::Utilities)
-        .with_id("org.aroz.zoraxy.event_subscriber_example")
-        .with_name("Event Subscriber Example Plugin")
-        .with_author("Anthony Rubick")
-        .with_description(
-            "An example plugin for event subscriptions, will display all events in the UI",
-        )
-        .with_url("https://zoraxy.aroz.org")
-        .with_version((1, 0, 0));
-    IntroSpect::new(metadata)
-        .with_ui_path("/ui")
-        .with_subscriptions(
-            SubscriptionsSettings::new("/notifyme")
-                .add_event_subscription(
-                    EventName::BlacklistedIpBlocked,
-                    "This event is triggered when a blacklisted IP is blocked",
-                )
-                .add_event_subscription(
-                    EventName::BlacklistToggled,
-                    "This event is triggered when the blacklist is toggled for an access rule",
-                )
-                .add_event_subscription(
-                    EventName::AccessRuleCreated,
-                    "This event is triggered when a new access ruleset is created",
-                )
-                .add_event_subscription(
-                    EventName::CustomEvent,
-                    "This event is a custom event that can be emitted by any plugin, we subscribe to it to demonstrate a \"monitor\" plugin that can see all custom events emitted by other plugins",
-                ),
-        )
+struct EventSubscriberExample {
+    state: AppState,
+}
+
+impl ZoraxyPlugin for EventSubscriberExample {
+    // the Zoraxy version this example was verified with
+    const MIN_ZORAXY_VERSION: Option<ZoraxyVersion> = Some(ZoraxyVersion::new(3, 2, 9));
+
+    fn introspect() -> IntroSpect {
+        let metadata = PluginMetadata::new(PluginType::Utilities)
+            .with_id("org.aroz.zoraxy.event_subscriber_example")
+            .with_name("Event Subscriber Example Plugin")
+            .with_author("Anthony Rubick")
+            .with_description(
+                "An example plugin for event subscriptions, will display all events in the UI",
+            )
+            .with_url("https://zoraxy.aroz.org")
+            .with_version((1, 0, 0));
+        IntroSpect::new(metadata)
+            .with_ui_path("/ui")
+            .with_subscriptions(
+                SubscriptionsSettings::new("/notifyme")
+                    .add_event_subscription(
+                        EventName::BlacklistedIpBlocked,
+                        "This event is triggered when a blacklisted IP is blocked",
+                    )
+                    .add_event_subscription(
+                        EventName::BlacklistToggled,
+                        "This event is triggered when the blacklist is toggled for an access rule",
+                    )
+                    .add_event_subscription(
+                        EventName::AccessRuleCreated,
+                        "This event is triggered when a new access ruleset is created",
+                    )
+                    .add_event_subscription(
+                        EventName::CustomEvent,
+                        "This event is a custom event that can be emitted by any plugin, we subscribe to it to demonstrate a \"monitor\" plugin that can see all custom events emitted by other plugins",
+                    ),
+            )
+    }
+
+    fn configure(_spec: &ConfigureSpec) -> anyhow::Result<Self> {
+        Ok(Self {
+            state: AppState {
+                event_log: Arc::new(Mutex::new(Vec::new())),
+            },
+        })
+    }
+
+    fn build_router(&self, _spec: &ConfigureSpec) -> anyhow::Result<Router> {
+        Ok(Router::new()
+            .route("/notifyme/{event_name}", post(handle_event))
+            .with_state(self.state.clone()))
+    }
+
+    fn build_ui_router(&self, _spec: &ConfigureSpec) -> anyhow::Result<Option<Router>> {
+        Ok(Some(
+            Router::new()
+                .route("/", get(render_ui))
+                .with_state(self.state.clone()),
+        ))
+    }
+
+    fn debug_logging(_spec: &ConfigureSpec) -> bool {
+        true
+    }
 }
 
 #[derive(Clone)]
@@ -49,21 +84,7 @@ struct AppState {
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let runtime_cfg = serve_and_recv_spec(std::env::args().collect(), &instrospect())?;
-
-    init_tracing_subscriber(true);
-
-    let app = Router::new()
-        .route("/ui/", get(render_ui))
-        .route("/notifyme/{event_name}", post(handle_event));
-
-    let state = AppState {
-        event_log: Arc::new(Mutex::new(Vec::new())),
-    };
-
-    let addr: SocketAddr = format!("127.0.0.1:{}", runtime_cfg.port).parse()?;
-    tracing::info!("Event Subscriber Example Plugin UI ready at http://{addr}");
-    start_plugin(app, state, addr, Some("/ui")).await
+    run::<EventSubscriberExample>().await
 }
 
 #[debug_handler]
@@ -95,7 +116,7 @@ async fn render_ui(State(state): State<AppState>) -> Html<String> {
         for event in log.iter() {
             let raw_event_data = serde_json::to_string_pretty(event).unwrap_or_default();
             let formatted_timestamp =
-                chrono::DateTime::<chrono::Utc>::from_timestamp(event.timestamp as i64, 0)
+                chrono::DateTime::<chrono::Utc>::from_timestamp(event.timestamp, 0)
                     .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
                     .unwrap_or_else(|| "Invalid timestamp".to_string());
             event_log_html.push_str(&format!(
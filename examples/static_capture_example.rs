@@ -1,5 +1,4 @@
-use std::net::SocketAddr;
-use std::sync::Arc;
+use std::sync::{Arc, LazyLock};
 
 use axum::Router;
 use axum::body::Body;
@@ -9,26 +8,75 @@ use axum::response::Html;
 use axum::routing::get;
 use zoraxy_rs::prelude::*;
 
-const UI_PATH: &str = "/ui";
-const UI_PATH_SLASH: &str = "/ui/";
 const STATIC_CAPTURE_INGRESS: &str = "/s_capture";
 const STATIC_CAPTURE_INGRESS_SLASH: &str = "/s_capture/";
 
-fn introspect() -> IntroSpect {
-    let metadata = PluginMetadata::new(PluginType::Router)
-        .with_id("org.aroz.zoraxy.static-capture-example")
-        .with_name("Zoraxy Static Capture Example Plugin")
-        .with_author("aroz.org")
-        .with_contact("https://aroz.org")
-        .with_description("An example Zoraxy plugin demonstrating static path capture routing.")
-        .with_url("https://zoraxy.aroz.org")
-        .with_version((1, 0, 0));
-    let settings = StaticCaptureSettings::new(STATIC_CAPTURE_INGRESS)
-        .add_static_capture_path("/test_a")
-        .add_static_capture_path("/test_b");
-    IntroSpect::new(metadata)
-        .with_static_capture_settings(settings)
-        .with_ui_path(UI_PATH)
+static METRICS: LazyLock<Arc<CaptureMetrics>> = LazyLock::new(|| Arc::new(CaptureMetrics::new()));
+
+/// The capture router, shared so the paths `introspect` advertises are those of the handlers `build_router` serves
+static CAPTURE_ROUTER: LazyLock<Arc<StaticPathRouter>> =
+    LazyLock::new(|| Arc::new(capture_router().with_metrics(METRICS.clone())));
+
+struct StaticCaptureExample {
+    router: Arc<StaticPathRouter>,
+    metrics: Arc<CaptureMetrics>,
+}
+
+impl ZoraxyPlugin for StaticCaptureExample {
+    fn introspect() -> IntroSpect {
+        let metadata = PluginMetadata::new(PluginType::Router)
+            .with_id("org.aroz.zoraxy.static-capture-example")
+            .with_name("Zoraxy Static Capture Example Plugin")
+            .with_author("aroz.org")
+            .with_contact("https://aroz.org")
+            .with_description("An example Zoraxy plugin demonstrating static path capture routing.")
+            .with_url("https://zoraxy.aroz.org")
+            .with_version((1, 0, 0));
+        // The capture paths are derived from the handlers registered on the router
+        let settings = CAPTURE_ROUTER.static_capture_settings(STATIC_CAPTURE_INGRESS);
+        IntroSpect::new(metadata)
+            .with_static_capture_settings(settings)
+            .with_ui_path("/ui")
+    }
+
+    fn configure(_spec: &ConfigureSpec) -> anyhow::Result<Self> {
+        Ok(Self {
+            router: CAPTURE_ROUTER.clone(),
+            metrics: METRICS.clone(),
+        })
+    }
+
+    fn build_router(&self, _spec: &ConfigureSpec) -> anyhow::Result<Router> {
+        let static_capture = self.router.clone().into_capture_service();
+
+        Ok(Router::new().route_service(STATIC_CAPTURE_INGRESS_SLASH, static_capture))
+    }
+
+    fn build_ui_router(&self, _spec: &ConfigureSpec) -> anyhow::Result<Option<Router>> {
+        Ok(Some(
+            Router::new()
+                .route("/", get(render_debug_ui))
+                .route("/metrics", self.metrics.prometheus_route()),
+        ))
+    }
+
+    fn static_path_router(&self) -> Option<Arc<StaticPathRouter>> {
+        Some(self.router.clone())
+    }
+
+    fn debug_logging(_spec: &ConfigureSpec) -> bool {
+        true
+    }
+}
+
+fn capture_router() -> StaticPathRouter {
+    let path_router = StaticPathRouter::new(default_handler.into_service());
+
+    path_router.register_path_service("/test_a", handler_a.into_service());
+
+    path_router.register_path_service("/test_b", handler_b.into_service());
+
+    path_router
 }
 
 async fn default_handler(req: Request<Body>) -> Html<String> {
@@ -54,26 +102,7 @@ async fn handler_b(req: Request<Body>) -> Html<String> {
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let runtime_cfg = serve_and_recv_spec(std::env::args().collect(), &introspect())?;
-
-    init_tracing_subscriber(true);
-
-    let mut path_router = StaticPathRouter::new(default_handler.into_service());
-
-    path_router.register_path_service("/test_a", handler_a.into_service());
-
-    path_router.register_path_service("/test_b", handler_b.into_service());
-
-    let static_capture = Arc::new(path_router).into_capture_service();
-
-    let app = Router::new()
-        .route(UI_PATH_SLASH, get(render_debug_ui))
-        .route_service(STATIC_CAPTURE_INGRESS_SLASH, static_capture);
-
-    let addr: SocketAddr = format!("127.0.0.1:{}", runtime_cfg.port).parse()?;
-    tracing::info!("Static capture example listening on http://{}", addr);
-
-    start_plugin(app, (), addr, Some(UI_PATH)).await
+    run::<StaticCaptureExample>().await
 }
 
 async fn render_debug_ui(req: Request<Body>) -> Html<String> {
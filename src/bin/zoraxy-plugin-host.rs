@@ -0,0 +1,194 @@
+//! Mock Zoraxy host for testing plugins end-to-end without a Zoraxy instance.
+//!
+//! ```text
+//! zoraxy-plugin-host <PLUGIN> [--request "[METHOD] URI"]... [--event <FILE|JSON>]... [--listen <ADDR>]
+//! ```
+//!
+//! * `--request` routes a request through the plugin's capture rules and prints the response
+//! * `--event` POSTs an `Event`, read from a JSON file or given inline, to the plugin's subscription path
+//! * `--listen` keeps the plugin running and routes every request received on `ADDR` through its capture rules
+//!
+//! The plugin is shut down via its `/term` route once all requests and events have been sent,
+//! or when the host receives Ctrl-C in `--listen` mode.
+
+use std::net::SocketAddr;
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use anyhow::{Context, Result, bail};
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{Method, Request, StatusCode};
+use axum::response::IntoResponse;
+use zoraxy_rs::host::{Capture, PluginHost};
+use zoraxy_rs::{Event, init_tracing_subscriber};
+
+const USAGE: &str = "usage: zoraxy-plugin-host <PLUGIN> [--request \"[METHOD] URI\"]... [--event <FILE|JSON>]... [--listen <ADDR>]";
+
+struct Args {
+    plugin: String,
+    requests: Vec<(Method, String)>,
+    events: Vec<Event>,
+    listen: Option<SocketAddr>,
+}
+
+fn parse_args(args: Vec<String>) -> Result<Args> {
+    let mut plugin = None;
+    let mut requests = Vec::new();
+    let mut events = Vec::new();
+    let mut listen = None;
+
+    let mut args = args.into_iter().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--request" => {
+                let value = args.next().context("no value provided after --request")?;
+                requests.push(parse_request(&value)?);
+            }
+            "--event" => {
+                let value = args.next().context("no value provided after --event")?;
+                events.push(parse_event(&value)?);
+            }
+            "--listen" => {
+                let value = args.next().context("no value provided after --listen")?;
+                listen = Some(value.parse().context("invalid --listen address")?);
+            }
+            "-h" | "--help" => bail!(USAGE),
+            _ if plugin.is_none() => plugin = Some(arg),
+            _ => bail!("unexpected argument {arg:?}\n{USAGE}"),
+        }
+    }
+
+    Ok(Args {
+        plugin: plugin.context(USAGE)?,
+        requests,
+        events,
+        listen,
+    })
+}
+
+fn parse_request(value: &str) -> Result<(Method, String)> {
+    match value.split_once(' ') {
+        Some((method, uri)) => Ok((
+            method.parse().context("invalid request method")?,
+            uri.trim().to_string(),
+        )),
+        None => Ok((Method::GET, value.to_string())),
+    }
+}
+
+fn parse_event(value: &str) -> Result<Event> {
+    let json = if value.trim_start().starts_with('{') {
+        value.to_string()
+    } else {
+        std::fs::read_to_string(value).with_context(|| format!("failed to read event {value}"))?
+    };
+    serde_json::from_str(&json).context("invalid event JSON")
+}
+
+fn describe(capture: &Capture) -> String {
+    match capture {
+        Capture::Static(capture_path) => format!("static capture {capture_path}"),
+        Capture::Dynamic => "dynamic capture".to_string(),
+        Capture::NotCaptured => "not captured".to_string(),
+    }
+}
+
+async fn forward(State(host): State<Arc<PluginHost>>, req: Request<Body>) -> impl IntoResponse {
+    let method = req.method().clone();
+    let uri = req.uri().clone();
+    match host.request(req).await {
+        Ok(resp) => {
+            tracing::info!(
+                "{method} {uri} -> {}: {}",
+                describe(&resp.capture),
+                resp.status
+            );
+            resp.into_response()
+        }
+        Err(err) => (StatusCode::BAD_GATEWAY, err.to_string()).into_response(),
+    }
+}
+
+async fn run(args: Args) -> Result<bool> {
+    let host = PluginHost::launch(&args.plugin).await?;
+    let metadata = host.intro_spect().metadata();
+    tracing::info!(
+        "Plugin {} ({}) listening on 127.0.0.1:{}",
+        metadata.name(),
+        metadata.id(),
+        host.configure_spec().port
+    );
+
+    let mut success = true;
+    for (method, uri) in args.requests {
+        let req = Request::builder()
+            .method(method.clone())
+            .uri(&uri)
+            .body(Body::empty())
+            .context("invalid request")?;
+        match host.request(req).await {
+            Ok(resp) => {
+                println!(
+                    "{method} {uri} -> {}: {}",
+                    describe(&resp.capture),
+                    resp.status
+                );
+                println!("{}", String::from_utf8_lossy(&resp.body));
+            }
+            Err(err) => {
+                println!("{method} {uri} -> error: {err}");
+                success = false;
+            }
+        }
+    }
+
+    for event in args.events {
+        match host.deliver_event(&event).await {
+            Ok(Some(status)) => println!("event {} -> {status}", event.name),
+            Ok(None) => println!("event {} -> not subscribed", event.name),
+            Err(err) => {
+                println!("event {} -> error: {err}", event.name);
+                success = false;
+            }
+        }
+    }
+
+    let host = Arc::new(host);
+    if let Some(addr) = args.listen {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        tracing::info!("Routing requests received on http://{addr} to the plugin, Ctrl-C to stop");
+        let app = axum::Router::new()
+            .fallback(forward)
+            .with_state(host.clone());
+        axum::serve(listener, app)
+            .with_graceful_shutdown(async {
+                let _ = tokio::signal::ctrl_c().await;
+            })
+            .await?;
+    }
+
+    let host = Arc::into_inner(host).context("plugin host is still in use")?;
+    let status = host.terminate().await?;
+    tracing::info!("Plugin exited with {status}");
+    Ok(success && status.success())
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    init_tracing_subscriber(false);
+
+    let result = match parse_args(std::env::args().collect()) {
+        Ok(args) => run(args).await,
+        Err(err) => Err(err),
+    };
+
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("{err:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
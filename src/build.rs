@@ -0,0 +1,202 @@
+//! Build script helper passing the `[package.metadata.zoraxy]` table of a plugin crate to [`plugin_metadata!`](crate::plugin_metadata).
+//!
+//! Cargo doesn't expose package metadata to the compiler, so the plugin's build script reads it
+//! from the manifest and passes it on as environment variables:
+//!
+//! ```toml
+//! [package.metadata.zoraxy]
+//! id = "com.example.myplugin"
+//! contact = "admin@example.com"
+//!
+//! [build-dependencies]
+//! zoraxy-rs = { version = "0.1", default-features = false, features = ["build"] }
+//! ```
+//!
+//! ```no_run
+//! // in the `main` function of build.rs
+//! zoraxy_rs::build::emit_plugin_metadata();
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+/// Environment variable holding the plugin id, read by `plugin_metadata!`
+pub const PLUGIN_ID_ENV: &str = "ZORAXY_PLUGIN_ID";
+/// Environment variable holding the plugin contact, read by `plugin_metadata!`
+pub const PLUGIN_CONTACT_ENV: &str = "ZORAXY_PLUGIN_CONTACT";
+
+/// Errors returned when reading the `[package.metadata.zoraxy]` table
+#[derive(Debug, thiserror::Error)]
+pub enum MetadataError {
+    /// The manifest could not be read
+    #[error("failed to read {path}: {source}")]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    /// The manifest is not valid TOML, or the table has an unexpected shape
+    #[error("failed to parse manifest: {0}")]
+    Parse(#[from] toml::de::Error),
+    /// The table has no `id`, or it is empty
+    #[error("missing plugin id, set `id` in the [package.metadata.zoraxy] table of Cargo.toml")]
+    MissingId,
+}
+
+/// The `[package.metadata.zoraxy]` table of a plugin crate
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ZoraxyTable {
+    pub id: String,
+    pub contact: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    package: Package,
+}
+
+#[derive(Deserialize)]
+struct Package {
+    #[serde(default)]
+    metadata: PackageMetadata,
+}
+
+#[derive(Default, Deserialize)]
+struct PackageMetadata {
+    zoraxy: Option<PartialZoraxyTable>,
+}
+
+#[derive(Deserialize)]
+struct PartialZoraxyTable {
+    id: Option<String>,
+    contact: Option<String>,
+}
+
+/// Parse the `[package.metadata.zoraxy]` table from the contents of a Cargo manifest
+///
+/// # Errors
+/// * Returns an error if the manifest is not valid TOML
+/// * Returns an error if the table has no `id`, or it is empty
+pub fn parse_zoraxy_table(manifest: &str) -> Result<ZoraxyTable, MetadataError> {
+    let manifest: Manifest = toml::from_str(manifest)?;
+    let table = manifest
+        .package
+        .metadata
+        .zoraxy
+        .ok_or(MetadataError::MissingId)?;
+    let id = table
+        .id
+        .filter(|id| !id.trim().is_empty())
+        .ok_or(MetadataError::MissingId)?;
+    Ok(ZoraxyTable {
+        id,
+        contact: table.contact.filter(|contact| !contact.trim().is_empty()),
+    })
+}
+
+/// Read the `[package.metadata.zoraxy]` table from the manifest at `path`
+///
+/// # Errors
+/// * Returns an error if the manifest can't be read, see [`parse_zoraxy_table`] for the other errors
+pub fn read_zoraxy_table(path: &Path) -> Result<ZoraxyTable, MetadataError> {
+    let manifest = std::fs::read_to_string(path).map_err(|source| MetadataError::Read {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    parse_zoraxy_table(&manifest)
+}
+
+/// Read the `[package.metadata.zoraxy]` table of the crate being built, and pass it to `plugin_metadata!`
+///
+/// Call this from the plugin's build script.
+///
+/// # Panics
+/// * Panics, failing the build, if `CARGO_MANIFEST_DIR` isn't set or the table can't be read,
+///   see [`read_zoraxy_table`]
+pub fn emit_plugin_metadata() {
+    let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR").expect(
+        "CARGO_MANIFEST_DIR is not set, emit_plugin_metadata must be called from a build script",
+    );
+    let path = Path::new(&manifest_dir).join("Cargo.toml");
+    let table = read_zoraxy_table(&path).unwrap_or_else(|err| panic!("{err}"));
+
+    println!("cargo::rerun-if-changed={}", path.display());
+    println!("cargo::rustc-env={PLUGIN_ID_ENV}={}", table.id);
+    if let Some(contact) = table.contact {
+        println!("cargo::rustc-env={PLUGIN_CONTACT_ENV}={contact}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parses_zoraxy_table() {
+        let expected = ZoraxyTable {
+            id: "com.example.myplugin".to_string(),
+            contact: Some("admin@example.com".to_string()),
+        };
+        let manifests = [
+            r#"
+[package]
+name = "myplugin"
+
+[package.metadata.zoraxy] # the plugin metadata
+id = "com.example.myplugin" # the plugin id
+contact = 'admin@example.com'
+
+[dependencies]
+id = "not this one"
+"#,
+            r#"
+[package]
+name = "myplugin"
+
+[ package.metadata.zoraxy ]
+id = "com.example.myplugin"
+contact = "admin@example.com"
+"#,
+            r#"
+[package]
+name = "myplugin"
+
+[package.metadata]
+zoraxy = { id = "com.example.myplugin", contact = "admin@example.com" }
+"#,
+            r#"
+[package]
+name = "myplugin"
+
+[package.metadata]
+zoraxy.id = "com.example.myplugin"
+zoraxy.contact = "admin@example.com"
+"#,
+        ];
+
+        for manifest in manifests {
+            assert_eq!(parse_zoraxy_table(manifest).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn requires_an_id() {
+        for manifest in [
+            "[package]\nname = \"myplugin\"",
+            "[package]\nid = \"x\"",
+            "[package.metadata.zoraxy]\ncontact = \"admin@example.com\"",
+            "[package.metadata.zoraxy]\nid = \"\"",
+        ] {
+            assert!(matches!(
+                parse_zoraxy_table(manifest),
+                Err(MetadataError::MissingId)
+            ));
+        }
+        assert!(matches!(
+            parse_zoraxy_table("[package"),
+            Err(MetadataError::Parse(_))
+        ));
+    }
+}
@@ -0,0 +1,336 @@
+use std::collections::HashMap;
+
+use axum::http::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::ConfigureSpec;
+
+const PLUGIN_API_PREFIX: &str = "/plugin/api/";
+/// Body of the 404 Go's `http.ServeMux` answers unknown routes with
+const UNKNOWN_ROUTE_BODY: &str = "404 page not found";
+
+/// Errors returned by the [`ZoraxyClient`]
+#[derive(Debug, thiserror::Error)]
+pub enum ZoraxyApiError {
+    /// The `ConfigureSpec` did not contain an API key, the plugin has no permitted endpoints
+    #[error("no API key in configure spec, does the plugin declare any permitted API endpoints?")]
+    MissingApiKey,
+    /// The `ConfigureSpec` did not contain the port Zoraxy is running on
+    #[error("no Zoraxy port in configure spec")]
+    MissingZoraxyPort,
+    /// Zoraxy rejected the API key
+    #[error("Zoraxy rejected the API key for {endpoint}: {body}")]
+    InvalidApiKey { endpoint: String, body: String },
+    /// The endpoint is plugin-accessible, but was not declared in the plugin's permitted endpoints
+    #[error("{endpoint} is not a permitted endpoint for this plugin: {body}")]
+    UnpermittedEndpoint { endpoint: String, body: String },
+    /// The endpoint is not accessible to plugins at all, or doesn't exist
+    #[error("{endpoint} is not accessible to plugins: {body}")]
+    InaccessibleEndpoint { endpoint: String, body: String },
+    /// Zoraxy responded with an unexpected status code
+    #[error("unexpected response from {endpoint} ({status}): {body}")]
+    UnexpectedStatus {
+        endpoint: String,
+        status: StatusCode,
+        body: String,
+    },
+    /// The request could not be sent, or the response could not be read
+    #[error("failed to call {endpoint}: {source}")]
+    Transport {
+        endpoint: String,
+        #[source]
+        source: reqwest::Error,
+    },
+    /// The response body could not be decoded
+    #[error("failed to decode response from {endpoint}: {source}")]
+    Decode {
+        endpoint: String,
+        #[source]
+        source: serde_json::Error,
+    },
+}
+
+impl ZoraxyApiError {
+    fn from_status(endpoint: &str, status: StatusCode, body: String) -> Self {
+        let endpoint = endpoint.to_string();
+        let plugin_endpoint = endpoint.starts_with(PLUGIN_API_PREFIX);
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN if !plugin_endpoint => {
+                Self::InaccessibleEndpoint { endpoint, body }
+            }
+            StatusCode::UNAUTHORIZED => Self::InvalidApiKey { endpoint, body },
+            StatusCode::FORBIDDEN => Self::UnpermittedEndpoint { endpoint, body },
+            // a 404 from a known route is about the requested resource, keep its status
+            StatusCode::NOT_FOUND if body.trim() == UNKNOWN_ROUTE_BODY => {
+                Self::InaccessibleEndpoint { endpoint, body }
+            }
+            status => Self::UnexpectedStatus {
+                endpoint,
+                status,
+                body,
+            },
+        }
+    }
+}
+
+/// Client for the plugin-accessible Zoraxy APIs
+///
+/// Requests are authenticated with the API key Zoraxy hands to the plugin in the `ConfigureSpec`,
+/// only endpoints declared with `IntroSpect::add_permitted_api_endpoint` can be called.
+#[derive(Debug, Clone)]
+pub struct ZoraxyClient {
+    http: reqwest::Client,
+    base_url: String,
+    api_key: String,
+}
+
+impl ZoraxyClient {
+    /// Create a new `ZoraxyClient` for a Zoraxy instance listening on `localhost:zoraxy_port`
+    #[must_use]
+    pub fn new<S: AsRef<str>>(zoraxy_port: u16, api_key: S) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: format!("http://localhost:{zoraxy_port}"),
+            api_key: api_key.as_ref().to_string(),
+        }
+    }
+
+    /// Create a new `ZoraxyClient` from the `ConfigureSpec` received from Zoraxy
+    ///
+    /// # Errors
+    /// * `ZoraxyApiError::MissingApiKey` if the spec has no API key
+    /// * `ZoraxyApiError::MissingZoraxyPort` if the spec has no Zoraxy port
+    pub fn from_spec(spec: &ConfigureSpec) -> Result<Self, ZoraxyApiError> {
+        let api_key = spec.api_key.as_ref().ok_or(ZoraxyApiError::MissingApiKey)?;
+        let zoraxy_port = spec.zoraxy_port.ok_or(ZoraxyApiError::MissingZoraxyPort)?;
+        Ok(Self::new(zoraxy_port, api_key))
+    }
+
+    /// Set the base URL of the Zoraxy instance (e.g. `http://127.0.0.1:8000`)
+    #[must_use]
+    pub fn with_base_url<S: AsRef<str>>(mut self, base_url: S) -> Self {
+        self.base_url = base_url.as_ref().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Set the API key used to authenticate requests
+    #[must_use]
+    pub fn with_api_key<S: AsRef<str>>(mut self, api_key: S) -> Self {
+        self.api_key = api_key.as_ref().to_string();
+        self
+    }
+
+    /// Set the underlying `reqwest::Client`
+    #[must_use]
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// The base URL requests are sent to, without a trailing slash
+    #[must_use]
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Send an authenticated GET request to `endpoint` and return the response body as text
+    ///
+    /// # Errors
+    /// * Returns an error if the request fails or Zoraxy responds with a non-success status code
+    pub async fn get_text(&self, endpoint: &str) -> Result<String, ZoraxyApiError> {
+        let transport = |source| ZoraxyApiError::Transport {
+            endpoint: endpoint.to_string(),
+            source,
+        };
+
+        let resp = self
+            .http
+            .get(format!("{}{endpoint}", self.base_url))
+            .bearer_auth(&self.api_key)
+            .send()
+            .await
+            .map_err(transport)?;
+        let status = resp.status();
+        let body = resp.text().await.map_err(transport)?;
+
+        if status.is_success() {
+            Ok(body)
+        } else {
+            Err(ZoraxyApiError::from_status(endpoint, status, body))
+        }
+    }
+
+    /// Send an authenticated GET request to `endpoint` and decode the JSON response body
+    ///
+    /// # Errors
+    /// * Returns an error if the request fails, Zoraxy responds with a non-success status code,
+    ///   or the response body can't be decoded as `T`
+    pub async fn get_json<T: DeserializeOwned>(&self, endpoint: &str) -> Result<T, ZoraxyApiError> {
+        let body = self.get_text(endpoint).await?;
+        serde_json::from_str(&body).map_err(|source| ZoraxyApiError::Decode {
+            endpoint: endpoint.to_string(),
+            source,
+        })
+    }
+
+    /// List all access rules, requires `GET /plugin/api/access/list` to be permitted
+    ///
+    /// # Errors
+    /// See [`ZoraxyClient::get_json`]
+    pub async fn access_list(&self) -> Result<Vec<AccessRule>, ZoraxyApiError> {
+        self.get_json("/plugin/api/access/list").await
+    }
+
+    /// List all proxy rules, requires `GET /plugin/api/proxy/list` to be permitted
+    ///
+    /// # Errors
+    /// See [`ZoraxyClient::get_json`]
+    pub async fn proxy_list(&self) -> Result<Vec<ProxyEndpoint>, ZoraxyApiError> {
+        self.get_json("/plugin/api/proxy/list").await
+    }
+}
+
+/// An access rule, as returned by `/plugin/api/access/list`
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct AccessRule {
+    #[serde(rename = "ID", default)]
+    pub id: String,
+    #[serde(rename = "Name", default)]
+    pub name: String,
+    #[serde(rename = "Desc", default)]
+    pub desc: String,
+    #[serde(rename = "BlacklistEnabled", default)]
+    pub blacklist_enabled: bool,
+    #[serde(rename = "WhitelistEnabled", default)]
+    pub whitelist_enabled: bool,
+    /// Remaining fields of the access rule
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+/// A proxy endpoint, as returned by `/plugin/api/proxy/list`
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ProxyEndpoint {
+    #[serde(rename = "RootOrMatchingDomain", default)]
+    pub root_or_matching_domain: String,
+    #[serde(rename = "MatchingDomainAlias", default)]
+    pub matching_domain_alias: Option<Vec<String>>,
+    #[serde(rename = "Disabled", default)]
+    pub disabled: bool,
+    /// Remaining fields of the proxy endpoint
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::http::HeaderMap;
+    use axum::routing::get;
+    use pretty_assertions::assert_eq;
+
+    const API_KEY: &str = "test-key";
+
+    async fn access_list(headers: HeaderMap) -> (StatusCode, String) {
+        if headers
+            .get("authorization")
+            .is_none_or(|v| v != format!("Bearer {API_KEY}").as_str())
+        {
+            return (StatusCode::UNAUTHORIZED, "Unauthorized".to_string());
+        }
+        (
+            StatusCode::OK,
+            r#"[{"ID":"default","Name":"Default","Desc":"Default access rule","BlacklistEnabled":true,"WhitelistEnabled":false,"BlackListIP":{}}]"#.to_string(),
+        )
+    }
+
+    async fn spawn_mock_zoraxy() -> ZoraxyClient {
+        let app = Router::new()
+            .route("/plugin/api/access/list", get(access_list))
+            .route(
+                "/plugin/api/proxy/list",
+                get(async || (StatusCode::FORBIDDEN, "Forbidden")),
+            )
+            .route(
+                "/plugin/api/proxy/detail",
+                get(async || (StatusCode::NOT_FOUND, "proxy rule not found")),
+            )
+            .route(
+                "/api/acme/listExpiredDomains",
+                get(async || (StatusCode::UNAUTHORIZED, "Unauthorized")),
+            )
+            .route(
+                "/api/stats/summary",
+                get(async || (StatusCode::INTERNAL_SERVER_ERROR, "boom")),
+            )
+            .fallback(async || (StatusCode::NOT_FOUND, "404 page not found\n"));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        ZoraxyClient::new(port, API_KEY).with_base_url(format!("http://127.0.0.1:{port}"))
+    }
+
+    #[tokio::test]
+    async fn decodes_access_list() {
+        let client = spawn_mock_zoraxy().await;
+
+        let rules = client.access_list().await.unwrap();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].id, "default");
+        assert!(rules[0].blacklist_enabled);
+        assert!(rules[0].extra.contains_key("BlackListIP"));
+    }
+
+    #[tokio::test]
+    async fn maps_error_statuses() {
+        let client = spawn_mock_zoraxy().await;
+
+        let invalid_key = client.clone().with_api_key("invalid-key");
+        assert!(matches!(
+            invalid_key.access_list().await,
+            Err(ZoraxyApiError::InvalidApiKey { .. })
+        ));
+        assert!(matches!(
+            client.proxy_list().await,
+            Err(ZoraxyApiError::UnpermittedEndpoint { .. })
+        ));
+        assert!(matches!(
+            client.get_text("/api/acme/listExpiredDomains").await,
+            Err(ZoraxyApiError::InaccessibleEndpoint { .. })
+        ));
+        assert!(matches!(
+            client.get_text("/plugin/api/unknown").await,
+            Err(ZoraxyApiError::InaccessibleEndpoint { .. })
+        ));
+        assert!(matches!(
+            client.get_text("/plugin/api/proxy/detail").await,
+            Err(ZoraxyApiError::UnexpectedStatus {
+                status: StatusCode::NOT_FOUND,
+                ..
+            })
+        ));
+        assert!(matches!(
+            client.get_text("/api/stats/summary").await,
+            Err(ZoraxyApiError::UnexpectedStatus {
+                status: StatusCode::INTERNAL_SERVER_ERROR,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn from_spec_requires_api_key() {
+        let spec: ConfigureSpec = serde_json::from_str(
+            r#"{"port":8080,"runtime_const":{"zoraxy_version":"3.2.9","zoraxy_uuid":"x","development_build":false},"zoraxy_port":8000}"#,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            ZoraxyClient::from_spec(&spec),
+            Err(ZoraxyApiError::MissingApiKey)
+        ));
+    }
+}
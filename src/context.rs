@@ -0,0 +1,261 @@
+//! Request context for captured traffic, see [`ZoraxyRequestContext`].
+
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+
+use axum::extract::FromRequestParts;
+use axum::http::header::{FORWARDED, HOST};
+use axum::http::request::Parts;
+use axum::http::{HeaderMap, Uri};
+
+use crate::dynamic_router::REQUEST_ID_HEADER;
+use crate::static_router::ORIGINAL_URI_HEADER;
+
+const REAL_IP_HEADER: &str = "x-real-ip";
+const FORWARDED_FOR_HEADER: &str = "x-forwarded-for";
+const FORWARDED_HOST_HEADER: &str = "x-forwarded-host";
+
+/// What Zoraxy knows about a captured request, for static and dynamic capture handlers alike
+///
+/// Captured by `StaticPathRouter` and `DynamicCaptureService` before they rewrite the request URI.
+/// Outside of them the extractor falls back to reading the request as it is.
+///
+/// ```
+/// use zoraxy_rs::prelude::*;
+///
+/// async fn handler(ctx: ZoraxyRequestContext) -> String {
+///     format!(
+///         "{} requested {} via {:?}",
+///         ctx.client_ip().map_or("unknown".to_string(), |ip| ip.to_string()),
+///         ctx.original_uri(),
+///         ctx.host()
+///     )
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZoraxyRequestContext {
+    capture_path: Option<String>,
+    original_uri: Uri,
+    ingress_uri: Uri,
+    request_id: Option<String>,
+    client_ip: Option<IpAddr>,
+    host: Option<String>,
+}
+
+impl ZoraxyRequestContext {
+    /// Read the context from a request as it arrives at the plugin
+    pub(crate) fn new(uri: &Uri, headers: &HeaderMap) -> Self {
+        let original_uri = header_str(headers, ORIGINAL_URI_HEADER)
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_else(|| uri.clone());
+
+        Self {
+            capture_path: None,
+            original_uri,
+            ingress_uri: uri.clone(),
+            request_id: header_str(headers, REQUEST_ID_HEADER).map(ToString::to_string),
+            client_ip: client_ip(headers),
+            host: header_str(headers, FORWARDED_HOST_HEADER)
+                .or_else(|| header_str(headers, HOST.as_str()))
+                .map(ToString::to_string),
+        }
+    }
+
+    pub(crate) fn set_capture_path(&mut self, capture_path: String) {
+        self.capture_path = Some(capture_path);
+    }
+
+    pub(crate) fn set_original_uri(&mut self, original_uri: Uri) {
+        self.original_uri = original_uri;
+    }
+
+    /// The static capture path Zoraxy matched, `None` for dynamic capture
+    #[must_use]
+    pub fn capture_path(&self) -> Option<&str> {
+        self.capture_path.as_deref()
+    }
+
+    /// The URI the client requested from Zoraxy
+    #[must_use]
+    pub const fn original_uri(&self) -> &Uri {
+        &self.original_uri
+    }
+
+    /// The URI the request arrived at the plugin with, before it was rewritten, e.g. `/s_capture/`
+    #[must_use]
+    pub const fn ingress_uri(&self) -> &Uri {
+        &self.ingress_uri
+    }
+
+    /// The Zoraxy request id, shared between the sniff and the capture of a dynamically captured request
+    #[must_use]
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+
+    /// The address of the client, from the `X-Forwarded-For`, `Forwarded` or `X-Real-IP` headers
+    ///
+    /// Only Zoraxy, which proxies the request to the plugin, is trusted: it appends the address of the
+    /// connection it received to `X-Forwarded-For`, so the rightmost entry is used. Entries to the left
+    /// of it are sent by the client, or proxies in front of Zoraxy, and can be spoofed.
+    /// If Zoraxy itself sits behind another proxy, this is the address of that proxy.
+    #[must_use]
+    pub const fn client_ip(&self) -> Option<IpAddr> {
+        self.client_ip
+    }
+
+    /// The host the client requested, from the `X-Forwarded-Host` or `Host` headers
+    #[must_use]
+    pub fn host(&self) -> Option<&str> {
+        self.host.as_deref()
+    }
+}
+
+impl<S> FromRequestParts<S> for ZoraxyRequestContext
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<Self>()
+            .cloned()
+            .unwrap_or_else(|| Self::new(&parts.uri, &parts.headers)))
+    }
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+/// Read the client address appended by Zoraxy, the rightmost entry of the forwarding headers,
+/// see [`ZoraxyRequestContext::client_ip`]
+fn client_ip(headers: &HeaderMap) -> Option<IpAddr> {
+    let forwarded_for = || {
+        header_str(headers, FORWARDED_FOR_HEADER)
+            .and_then(|value| value.rsplit(',').next())
+            .and_then(parse_ip)
+    };
+    let forwarded = || {
+        header_str(headers, FORWARDED.as_str())
+            .and_then(|value| value.rsplit(',').next())
+            .and_then(|element| {
+                element.split(';').find_map(|pair| {
+                    let (key, value) = pair.split_once('=')?;
+                    key.trim()
+                        .eq_ignore_ascii_case("for")
+                        .then(|| value.trim().trim_matches('"'))
+                })
+            })
+            .and_then(parse_ip)
+    };
+    let real_ip = || header_str(headers, REAL_IP_HEADER).and_then(parse_ip);
+
+    forwarded_for().or_else(forwarded).or_else(real_ip)
+}
+
+/// Parse an IP address, with or without a port, IPv6 addresses may be in brackets
+fn parse_ip(value: &str) -> Option<IpAddr> {
+    let value = value.trim();
+    value
+        .parse::<IpAddr>()
+        .or_else(|_| value.parse::<SocketAddr>().map(|addr| addr.ip()))
+        .or_else(|_| value.trim_start_matches('[').trim_end_matches(']').parse())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use pretty_assertions::assert_eq;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| {
+                (
+                    axum::http::HeaderName::from_static(name),
+                    HeaderValue::from_static(value),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_client_ip_from_forwarded_headers() {
+        let v4 = Some(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7)));
+        let v6 = Some(IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)));
+
+        assert_eq!(client_ip(&headers(&[("x-real-ip", "203.0.113.7")])), v4);
+        assert_eq!(
+            client_ip(&headers(&[(
+                "x-forwarded-for",
+                "10.0.0.1, 203.0.113.7:5678"
+            )])),
+            v4
+        );
+        assert_eq!(
+            client_ip(&headers(&[(
+                "forwarded",
+                "for=10.0.0.1, proto=https;for=\"[2001:db8::1]:4711\""
+            )])),
+            v6
+        );
+        assert_eq!(
+            client_ip(&headers(&[
+                ("x-real-ip", "203.0.113.7"),
+                ("x-forwarded-for", "2001:db8::1")
+            ])),
+            v6
+        );
+        assert_eq!(client_ip(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn ignores_spoofed_forwarding_hops() {
+        let client = Some(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7)));
+
+        // the client claims to be 10.0.0.1 and forwarded by 192.0.2.1, Zoraxy appends the real address
+        assert_eq!(
+            client_ip(&headers(&[
+                ("x-real-ip", "10.0.0.1"),
+                ("x-forwarded-for", "10.0.0.1, 192.0.2.1, 203.0.113.7")
+            ])),
+            client
+        );
+        assert_eq!(
+            client_ip(&headers(&[(
+                "forwarded",
+                "for=10.0.0.1, for=192.0.2.1, for=203.0.113.7;proto=https"
+            )])),
+            client
+        );
+    }
+
+    #[test]
+    fn reads_context_from_headers() {
+        let ctx = ZoraxyRequestContext::new(
+            &"/s_capture/".parse().unwrap(),
+            &headers(&[
+                ("x-zoraxy-uri", "/test_a/x?y=1"),
+                ("x-zoraxy-requestid", "abc123"),
+                ("host", "127.0.0.1:5000"),
+                ("x-forwarded-host", "example.com"),
+            ]),
+        );
+
+        assert_eq!(ctx.original_uri(), "/test_a/x?y=1");
+        assert_eq!(ctx.ingress_uri(), "/s_capture/");
+        assert_eq!(ctx.request_id(), Some("abc123"));
+        assert_eq!(ctx.host(), Some("example.com"));
+        assert_eq!(ctx.capture_path(), None);
+    }
+}
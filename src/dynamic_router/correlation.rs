@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use axum::extract::{FromRequestParts, OptionalFromRequestParts};
+use axum::http::request::Parts;
+use axum::http::{Extensions, StatusCode};
+use axum::response::{IntoResponse, Response};
+
+use super::DynamicSniffForwardRequest;
+
+/// Data the sniff handler attached to a request id, for the capture handler of the same request
+///
+/// Zoraxy sends the sniff and the capture of a request separately, sharing only the request id.
+/// A `SniffStore` carries data from one to the other, e.g. a tenant looked up while sniffing:
+/// the sniff handler [`attach`](Self::attach)es it to accepted requests, and a `DynamicCaptureService`
+/// set up [`with_sniff_store`](super::DynamicCaptureService::with_sniff_store) hands it to the capture
+/// handler as [`SniffData`].
+///
+/// Entries are taken by the capture of their request, entries whose request never arrives
+/// are dropped after the TTL.
+///
+/// ```
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// use axum::Router;
+/// use axum::extract::State;
+/// use axum::handler::HandlerWithoutStateExt;
+/// use axum::routing::post;
+/// use zoraxy_rs::prelude::*;
+///
+/// #[derive(Clone)]
+/// struct Tenant(String);
+///
+/// async fn sniff(
+///     State(store): State<Arc<SniffStore<Tenant>>>,
+///     sniff: DynamicSniffForwardRequest,
+/// ) -> SniffDecision {
+///     let tenant = Tenant(sniff.hostname.clone());
+///     store.attach(&sniff, tenant);
+///     SniffDecision::Accept
+/// }
+///
+/// async fn capture(SniffData(tenant): SniffData<Tenant>) -> String {
+///     format!("hello {}", tenant.0)
+/// }
+///
+/// let store = Arc::new(SniffStore::new(Duration::from_secs(30)));
+/// let capture_service = DynamicCaptureService::new("/d_capture/", capture.into_service())
+///     .with_sniff_store(store.clone());
+/// let router: Router = Router::new()
+///     .route("/d_sniff/", post(sniff))
+///     .with_state(store)
+///     .nest_service("/d_capture/", capture_service);
+/// ```
+#[derive(Debug)]
+pub struct SniffStore<T> {
+    ttl: Duration,
+    state: Mutex<StoreState<T>>,
+}
+
+#[derive(Debug)]
+struct StoreState<T> {
+    entries: HashMap<String, (Instant, T)>,
+    /// When expired entries are next swept, so that a store only holds about two TTLs of entries
+    next_sweep: Instant,
+}
+
+impl<T> SniffStore<T> {
+    /// Create a store keeping entries for at most `ttl`
+    #[must_use]
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            state: Mutex::new(StoreState {
+                entries: HashMap::new(),
+                next_sweep: Instant::now() + ttl,
+            }),
+        }
+    }
+
+    #[must_use]
+    pub const fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Store `value` for the request with the id `request_id`, replacing any previous value
+    pub fn insert(&self, request_id: impl Into<String>, value: T) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if now >= state.next_sweep {
+            state.entries.retain(|_, (expires, _)| *expires > now);
+            state.next_sweep = now + self.ttl;
+        }
+        state
+            .entries
+            .insert(request_id.into(), (now + self.ttl, value));
+    }
+
+    /// Store `value` for the sniffed request, returns `false` if the sniff carried no request id
+    pub fn attach(&self, sniff: &DynamicSniffForwardRequest, value: T) -> bool {
+        let Some(request_id) = sniff.request_uuid() else {
+            tracing::warn!("sniff request has no request id, not storing sniff data");
+            return false;
+        };
+        self.insert(request_id, value);
+        true
+    }
+
+    /// Remove and return the value stored for `request_id`, unless it expired
+    pub fn take(&self, request_id: &str) -> Option<T> {
+        let (expires, value) = self
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entries
+            .remove(request_id)?;
+        (expires > Instant::now()).then_some(value)
+    }
+}
+
+/// A `SniffStore` of any type, so that a `DynamicCaptureService` can hold several of them
+pub(super) trait SniffDataSource: Send + Sync {
+    /// Move the data stored for `request_id` into the request extensions as `SniffData`
+    fn take_into(&self, request_id: &str, extensions: &mut Extensions);
+}
+
+impl<T> SniffDataSource for SniffStore<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    fn take_into(&self, request_id: &str, extensions: &mut Extensions) {
+        if let Some(value) = self.take(request_id) {
+            extensions.insert(SniffData(value));
+        }
+    }
+}
+
+/// Extractor for the data the sniff handler stored in a [`SniffStore`] for the captured request
+///
+/// Use `Option<SniffData<T>>` if the sniff handler doesn't store data for every accepted request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SniffData<T>(pub T);
+
+/// Rejection for [`SniffData`] if no data was stored for the request, or it expired
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "no sniff data for this request, it was not stored, has expired or the capture service has no matching SniffStore"
+)]
+pub struct MissingSniffData;
+
+impl IntoResponse for MissingSniffData {
+    fn into_response(self) -> Response {
+        (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()).into_response()
+    }
+}
+
+impl<S, T> FromRequestParts<S> for SniffData<T>
+where
+    S: Send + Sync,
+    T: Clone + Send + Sync + 'static,
+{
+    type Rejection = MissingSniffData;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Self>()
+            .cloned()
+            .ok_or(MissingSniffData)
+    }
+}
+
+impl<S, T> OptionalFromRequestParts<S> for SniffData<T>
+where
+    S: Send + Sync,
+    T: Clone + Send + Sync + 'static,
+{
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Option<Self>, Self::Rejection> {
+        Ok(parts.extensions.get::<Self>().cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takes_values_once_and_expires_them() {
+        let store = SniffStore::new(Duration::from_secs(60));
+        store.insert("a", 1);
+        assert_eq!(store.take("a"), Some(1));
+        assert_eq!(store.take("a"), None);
+
+        let store = SniffStore::new(Duration::ZERO);
+        store.insert("a", 1);
+        assert_eq!(store.take("a"), None);
+    }
+
+    #[test]
+    fn sweeps_expired_entries_on_insert() {
+        let store = SniffStore::new(Duration::ZERO);
+        for id in ["a", "b", "c"] {
+            store.insert(id, ());
+        }
+        assert_eq!(store.state.lock().unwrap().entries.len(), 1);
+    }
+}
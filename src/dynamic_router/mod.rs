@@ -0,0 +1,839 @@
+use axum::Router;
+use axum::body::{self, Body, Bytes};
+use axum::extract::FromRequest;
+use axum::http::header::{HeaderName, HeaderValue};
+use axum::http::{HeaderMap, Method, Request, StatusCode, Uri, Version};
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tower::util::BoxCloneSyncService;
+use tower::{Layer, Service};
+use tracing::warn;
+
+use crate::context::ZoraxyRequestContext;
+use crate::metrics::CaptureMetrics;
+use crate::types::ControlStatusCode;
+
+mod correlation;
+mod router;
+mod rules;
+
+use correlation::SniffDataSource;
+pub use correlation::{MissingSniffData, SniffData, SniffStore};
+pub use router::{DynamicRouter, DynamicRouterCaptureService, DynamicRouterSniffService};
+pub use rules::{SniffRule, SniffRuleError, SniffRules};
+
+pub(crate) const REQUEST_ID_HEADER: &str = "x-zoraxy-requestid";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DynamicSniffForwardRequest {
+    pub method: String,
+    pub hostname: String,
+    pub url: String,
+    pub header: HashMap<String, Vec<String>>,
+    pub remote_addr: String,
+    pub host: String,
+    pub request_uri: String,
+    pub proto: String,
+    pub proto_major: i32,
+    pub proto_minor: i32,
+    #[serde(skip)]
+    request_uuid: Option<String>,
+    #[serde(skip)]
+    raw_request: Option<Request<Body>>,
+}
+
+impl DynamicSniffForwardRequest {
+    pub fn request_uuid(&self) -> Option<&str> {
+        self.request_uuid.as_deref()
+    }
+
+    fn set_request_uuid(&mut self, value: Option<String>) {
+        self.request_uuid = value;
+    }
+
+    pub const fn raw_request(&self) -> Option<&Request<Body>> {
+        self.raw_request.as_ref()
+    }
+
+    fn set_raw_request(&mut self, req: Option<Request<Body>>) {
+        self.raw_request = req;
+    }
+
+    /// The request method
+    ///
+    /// # Errors
+    /// * Returns an error if the method is not a valid HTTP method token
+    pub fn http_method(&self) -> Result<Method, SniffFieldError> {
+        Method::from_bytes(self.method.as_bytes())
+            .map_err(|_| SniffFieldError::InvalidMethod(self.method.clone()))
+    }
+
+    /// The request headers, with case-insensitive names and all values of repeated headers
+    ///
+    /// # Errors
+    /// * Returns an error if a header name or value is not valid in HTTP
+    pub fn header_map(&self) -> Result<HeaderMap, SniffFieldError> {
+        let mut headers = HeaderMap::with_capacity(self.header.len());
+        for (name, values) in &self.header {
+            let header_name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| SniffFieldError::InvalidHeaderName(name.clone()))?;
+            for value in values {
+                let value = HeaderValue::from_str(value)
+                    .map_err(|_| SniffFieldError::InvalidHeaderValue { name: name.clone() })?;
+                headers.append(header_name.clone(), value);
+            }
+        }
+        Ok(headers)
+    }
+
+    /// The request URI as the client sent it, usually only its path and query
+    ///
+    /// # Errors
+    /// * Returns an error if `request_uri` is not a valid URI
+    pub fn uri(&self) -> Result<Uri, SniffFieldError> {
+        self.request_uri
+            .parse()
+            .map_err(|source| SniffFieldError::InvalidUri {
+                uri: self.request_uri.clone(),
+                source,
+            })
+    }
+
+    /// The percent-decoded query parameters of the request URI, in order, `+` is decoded as a space
+    ///
+    /// # Errors
+    /// * Returns an error if `request_uri` is not a valid URI
+    pub fn query_pairs(&self) -> Result<Vec<(String, String)>, SniffFieldError> {
+        let uri = self.uri()?;
+        let decode = |value: &str| {
+            percent_encoding::percent_decode_str(&value.replace('+', " "))
+                .decode_utf8_lossy()
+                .into_owned()
+        };
+        Ok(uri
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (decode(name), decode(value))
+            })
+            .collect())
+    }
+
+    /// The address the client connected to Zoraxy from, e.g. `192.0.2.1:51234` or `[2001:db8::1]:51234`
+    ///
+    /// # Errors
+    /// * Returns an error if `remote_addr` is not an IP address with a port
+    pub fn remote_socket_addr(&self) -> Result<SocketAddr, SniffFieldError> {
+        self.remote_addr
+            .parse()
+            .map_err(|_| SniffFieldError::InvalidRemoteAddr(self.remote_addr.clone()))
+    }
+
+    /// The IP address the client connected to Zoraxy from, `remote_addr` may also lack the port
+    ///
+    /// # Errors
+    /// * Returns an error if `remote_addr` is not an IP address, with or without a port
+    pub fn remote_ip(&self) -> Result<IpAddr, SniffFieldError> {
+        self.remote_socket_addr()
+            .map(|addr| addr.ip())
+            .or_else(|_| {
+                self.remote_addr
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .parse()
+            })
+            .map_err(|_| SniffFieldError::InvalidRemoteAddr(self.remote_addr.clone()))
+    }
+
+    /// The HTTP version of the request, from `proto_major` and `proto_minor`
+    ///
+    /// # Errors
+    /// * Returns an error if the version is not one of HTTP/0.9, 1.0, 1.1, 2 or 3
+    pub const fn http_version(&self) -> Result<Version, SniffFieldError> {
+        match (self.proto_major, self.proto_minor) {
+            (0, 9) => Ok(Version::HTTP_09),
+            (1, 0) => Ok(Version::HTTP_10),
+            (1, 1) => Ok(Version::HTTP_11),
+            (2, 0) => Ok(Version::HTTP_2),
+            (3, 0) => Ok(Version::HTTP_3),
+            (major, minor) => Err(SniffFieldError::UnsupportedVersion { major, minor }),
+        }
+    }
+}
+
+/// A field of a [`DynamicSniffForwardRequest`] that doesn't parse into its typed view
+#[derive(Debug, thiserror::Error)]
+pub enum SniffFieldError {
+    #[error("invalid request method {0:?}")]
+    InvalidMethod(String),
+    #[error("invalid header name {0:?}")]
+    InvalidHeaderName(String),
+    #[error("invalid value for header {name:?}")]
+    InvalidHeaderValue { name: String },
+    #[error("invalid request URI {uri:?}: {source}")]
+    InvalidUri {
+        uri: String,
+        source: axum::http::uri::InvalidUri,
+    },
+    #[error("invalid remote address {0:?}")]
+    InvalidRemoteAddr(String),
+    #[error("unsupported HTTP version {major}.{minor}")]
+    UnsupportedVersion { major: i32, minor: i32 },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SniffExtractorError {
+    #[error("sniff payload exceeds the limit of {limit} bytes")]
+    TooLarge { limit: usize },
+    #[error("failed to read sniff request body: {0}")]
+    Body(#[from] axum::Error),
+    #[error("failed to decode sniff payload: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+impl SniffExtractorError {
+    /// The status the error is answered with under [`SniffRejectionPolicy::Status`]
+    #[must_use]
+    pub const fn status_code(&self) -> StatusCode {
+        match self {
+            Self::TooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::Body(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Json(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl IntoResponse for SniffExtractorError {
+    fn into_response(self) -> Response {
+        (self.status_code(), self.to_string()).into_response()
+    }
+}
+
+/// How a sniff request that can't be read or decoded is answered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SniffRejectionPolicy {
+    /// Respond with 413 Payload Too Large for oversized payloads, 400 Bad Request for undecodable
+    /// payloads and 500 Internal Server Error if the body can't be read
+    #[default]
+    Status,
+    /// Respond with [`ControlStatusCode::Error`], so Zoraxy logs the failure and handles the request itself
+    ControlError,
+}
+
+/// Limits and rejection policy of the `DynamicSniffForwardRequest` extractor
+///
+/// The extractor reads the config from the request extensions and falls back to the defaults without one,
+/// so it only applies once it is set on the sniff side: with [`DynamicRouter::with_sniff_config`],
+/// [`SniffRules::with_sniff_config`] or, for a handler of your own, as an `axum::Extension` of the sniff route:
+///
+/// ```
+/// use axum::{Extension, Router, routing::post};
+/// use zoraxy_rs::prelude::*;
+///
+/// async fn sniff(sniff: DynamicSniffForwardRequest) -> SniffDecision {
+///     SniffDecision::Skip
+/// }
+///
+/// let config = SniffConfig::new()
+///     .with_body_limit(1024 * 1024)
+///     .with_rejection_policy(SniffRejectionPolicy::ControlError);
+/// let router: Router = Router::new().route("/d_sniff/", post(sniff).layer(Extension(config)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SniffConfig {
+    body_limit: usize,
+    rejection_policy: SniffRejectionPolicy,
+}
+
+impl Default for SniffConfig {
+    fn default() -> Self {
+        Self {
+            body_limit: DEFAULT_SNIFF_BODY_LIMIT,
+            rejection_policy: SniffRejectionPolicy::default(),
+        }
+    }
+}
+
+impl SniffConfig {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum size of a sniff payload in bytes, defaults to 256 KiB
+    #[must_use]
+    pub const fn with_body_limit(mut self, limit: usize) -> Self {
+        self.body_limit = limit;
+        self
+    }
+
+    /// Set how unreadable sniff requests are answered, defaults to [`SniffRejectionPolicy::Status`]
+    #[must_use]
+    pub const fn with_rejection_policy(mut self, policy: SniffRejectionPolicy) -> Self {
+        self.rejection_policy = policy;
+        self
+    }
+
+    #[must_use]
+    pub const fn body_limit(&self) -> usize {
+        self.body_limit
+    }
+
+    #[must_use]
+    pub const fn rejection_policy(&self) -> SniffRejectionPolicy {
+        self.rejection_policy
+    }
+
+    /// Read the sniff payload, rejecting it as configured
+    async fn read_body(
+        &self,
+        body: Body,
+        request_id: Option<&str>,
+    ) -> Result<Bytes, SniffRejection> {
+        body::to_bytes(body, self.body_limit)
+            .await
+            .map_err(|err| {
+                let err = err.into_inner();
+                if err.is::<http_body_util::LengthLimitError>() {
+                    SniffExtractorError::TooLarge {
+                        limit: self.body_limit,
+                    }
+                } else {
+                    SniffExtractorError::Body(axum::Error::new(err))
+                }
+            })
+            .map_err(|error| self.reject(error, request_id))
+    }
+
+    fn reject(&self, error: SniffExtractorError, request_id: Option<&str>) -> SniffRejection {
+        warn!(target: "zoraxy::dynamic_router", request_id, %error, "Rejecting sniff request");
+        SniffRejection {
+            error,
+            request_id: request_id.map(ToString::to_string),
+            policy: self.rejection_policy,
+        }
+    }
+}
+
+/// Rejection of the `DynamicSniffForwardRequest` extractor, answered according to the [`SniffRejectionPolicy`]
+#[derive(Debug, thiserror::Error)]
+#[error("{error}")]
+pub struct SniffRejection {
+    #[source]
+    error: SniffExtractorError,
+    request_id: Option<String>,
+    policy: SniffRejectionPolicy,
+}
+
+impl SniffRejection {
+    #[must_use]
+    pub const fn error(&self) -> &SniffExtractorError {
+        &self.error
+    }
+
+    #[must_use]
+    pub fn into_error(self) -> SniffExtractorError {
+        self.error
+    }
+
+    /// The Zoraxy request id of the rejected sniff
+    #[must_use]
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+}
+
+impl IntoResponse for SniffRejection {
+    fn into_response(self) -> Response {
+        match self.policy {
+            SniffRejectionPolicy::Status => self.error.into_response(),
+            SniffRejectionPolicy::ControlError => ControlStatusCode::Error.into_response(),
+        }
+    }
+}
+
+impl<S> FromRequest<S> for DynamicSniffForwardRequest
+where
+    S: Send + Sync + Clone,
+{
+    type Rejection = SniffRejection;
+
+    async fn from_request(req: axum::extract::Request, _: &S) -> Result<Self, Self::Rejection> {
+        let (mut parts, body) = req.into_parts();
+        let config = parts
+            .extensions
+            .get::<SniffConfig>()
+            .copied()
+            .unwrap_or_default();
+        let request_uuid = parts
+            .headers
+            .remove(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok().map(std::string::ToString::to_string));
+
+        let bytes = config.read_body(body, request_uuid.as_deref()).await?;
+        let mut payload: Self = serde_json::from_slice(&bytes)
+            .map_err(|err| config.reject(err.into(), request_uuid.as_deref()))?;
+        payload.set_request_uuid(request_uuid);
+        payload.set_raw_request(Some(Request::from_parts(parts, Body::from(bytes))));
+        Ok(payload)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SniffDecision {
+    Accept,
+    Skip,
+}
+
+impl IntoResponse for SniffDecision {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Accept => (StatusCode::OK, "OK").into_response(),
+            Self::Skip => (StatusCode::NOT_IMPLEMENTED, "SKIP").into_response(),
+        }
+    }
+}
+
+const DEFAULT_SNIFF_BODY_LIMIT: usize = 256 * 1024;
+
+#[derive(Clone)]
+pub struct DynamicCaptureService {
+    inner: BoxCloneSyncService<Request<Body>, Response, Infallible>,
+    ingress: String,
+    metrics: Option<Arc<CaptureMetrics>>,
+    sniff_stores: Vec<Arc<dyn SniffDataSource>>,
+}
+
+impl DynamicCaptureService {
+    pub fn new<H>(ingress: &str, handler: H) -> Self
+    where
+        H: Service<Request<Body>, Response = Response, Error = Infallible>
+            + Send
+            + Sync
+            + Clone
+            + 'static,
+        H::Future: Send + 'static,
+    {
+        Self {
+            inner: BoxCloneSyncService::new(handler),
+            ingress: normalize_ingress(ingress),
+            metrics: None,
+            sniff_stores: Vec::new(),
+        }
+    }
+
+    /// Create a `DynamicCaptureService` serving the captured requests with an axum `Router` and its state
+    ///
+    /// The router sees the requests with the ingress path stripped, i.e. with their original URI.
+    pub fn from_router<S>(ingress: &str, router: Router<S>, state: S) -> Self
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        Self::new(ingress, router.with_state(state))
+    }
+
+    /// Apply a `tower::Layer` to the capture handler, it sees the request after its path has been rewritten
+    ///
+    /// The layered service must not fail, handle errors of fallible middleware
+    /// with e.g. axum's `HandleErrorLayer`.
+    #[must_use]
+    pub fn layer<L>(self, layer: L) -> Self
+    where
+        L: Layer<BoxCloneSyncService<Request<Body>, Response, Infallible>>,
+        L::Service: Service<Request<Body>, Response = Response, Error = Infallible>
+            + Send
+            + Sync
+            + Clone
+            + 'static,
+        <L::Service as Service<Request<Body>>>::Future: Send + 'static,
+    {
+        Self {
+            inner: BoxCloneSyncService::new(layer.layer(self.inner)),
+            ..self
+        }
+    }
+
+    /// Record the capture handler latency in `metrics`, labelled with the ingress path
+    #[must_use]
+    pub fn with_metrics(mut self, metrics: Arc<CaptureMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Hand the data the sniff handler stored in `store` to the capture handler as [`SniffData`]
+    ///
+    /// Several stores of different types can be attached, the data is taken out of the store
+    /// by the request's id.
+    #[must_use]
+    pub fn with_sniff_store<T>(mut self, store: Arc<SniffStore<T>>) -> Self
+    where
+        T: Clone + Send + Sync + 'static,
+    {
+        self.sniff_stores.push(store);
+        self
+    }
+}
+
+impl Service<Request<Body>> for DynamicCaptureService {
+    type Response = Response;
+    type Error = Infallible;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Service::<Request<Body>>::poll_ready(&mut self.inner, cx)
+    }
+
+    fn call(&mut self, mut req: Request<Body>) -> Self::Future {
+        let mut context = ZoraxyRequestContext::new(req.uri(), req.headers());
+        rewrite_capture_request(&self.ingress, &mut req);
+        context.set_original_uri(req.uri().clone());
+        if let Some(request_id) = context.request_id() {
+            for store in &self.sniff_stores {
+                store.take_into(request_id, req.extensions_mut());
+            }
+        }
+        req.extensions_mut().insert(context);
+
+        let response = self.inner.call(req);
+        let Some(metrics) = self.metrics.clone() else {
+            return response;
+        };
+        let ingress = self.ingress.clone();
+        let start = Instant::now();
+        Box::pin(async move {
+            let response = response.await;
+            metrics.record_capture(&ingress, start.elapsed());
+            response
+        })
+    }
+}
+
+fn rewrite_capture_request(ingress: &str, req: &mut Request<Body>) {
+    let current_path = req.uri().path();
+    if !current_path.starts_with(ingress) {
+        return;
+    }
+
+    let remainder = &current_path[ingress.len()..];
+    let mut normalized = if remainder.is_empty() {
+        String::from('/')
+    } else {
+        format!("/{}", remainder.trim_start_matches('/'))
+    };
+
+    while normalized.contains("//") {
+        normalized = normalized.replace("//", "/");
+    }
+
+    if let Some(query) = req.uri().query() {
+        normalized.push('?');
+        normalized.push_str(query);
+    }
+
+    if let Ok(path_and_query) = normalized.parse() {
+        let mut parts = req.uri().clone().into_parts();
+        parts.path_and_query = Some(path_and_query);
+        if let Ok(new_uri) = Uri::from_parts(parts) {
+            *req.uri_mut() = new_uri;
+        }
+    }
+}
+
+fn normalize_ingress(value: &str) -> String {
+    let mut normalized = value.trim().to_string();
+    if !normalized.starts_with('/') {
+        normalized.insert(0, '/');
+    }
+    if !normalized.ends_with('/') {
+        normalized.push('/');
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::debug_handler;
+    use axum::extract::State;
+    use axum::handler::HandlerWithoutStateExt;
+    use axum::http::Request;
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn parses_sniff_payload_and_request_id() {
+        let body = json!({
+            "method": "GET",
+            "hostname": "example.com",
+            "url": "http://example.com/test",
+            "header": {},
+            "remote_addr": "127.0.0.1:8080",
+            "host": "example.com",
+            "request_uri": "/test",
+            "proto": "HTTP/1.1",
+            "proto_major": 1,
+            "proto_minor": 1
+        });
+        let req = Request::builder()
+            .uri("/sniff")
+            .header(REQUEST_ID_HEADER, "abc123")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+
+        let payload = DynamicSniffForwardRequest::from_request(req, &())
+            .await
+            .unwrap();
+        assert_eq!(payload.method, "GET");
+        assert_eq!(payload.request_uuid(), Some("abc123"));
+    }
+
+    #[test]
+    fn parses_typed_sniff_fields() {
+        let mut sniff: DynamicSniffForwardRequest = serde_json::from_value(json!({
+            "method": "PATCH",
+            "hostname": "example.com",
+            "url": "/test?a=1&b=x%20y+z&flag",
+            "header": {"X-Multi": ["1", "2"], "content-type": ["text/plain"]},
+            "remote_addr": "[2001:db8::1]:51234",
+            "host": "example.com",
+            "request_uri": "/test?a=1&b=x%20y+z&flag",
+            "proto": "HTTP/2.0",
+            "proto_major": 2,
+            "proto_minor": 0
+        }))
+        .unwrap();
+
+        assert_eq!(sniff.http_method().unwrap(), Method::PATCH);
+        let headers = sniff.header_map().unwrap();
+        assert_eq!(headers.get_all("x-multi").iter().count(), 2);
+        assert_eq!(headers["Content-Type"], "text/plain");
+        assert_eq!(sniff.uri().unwrap().path(), "/test");
+        assert_eq!(
+            sniff.query_pairs().unwrap(),
+            [
+                ("a".to_string(), "1".to_string()),
+                ("b".to_string(), "x y z".to_string()),
+                ("flag".to_string(), String::new()),
+            ]
+        );
+        assert_eq!(
+            sniff.remote_socket_addr().unwrap(),
+            "[2001:db8::1]:51234".parse::<SocketAddr>().unwrap()
+        );
+        assert_eq!(
+            sniff.remote_ip().unwrap(),
+            "2001:db8::1".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(sniff.http_version().unwrap(), Version::HTTP_2);
+
+        sniff.remote_addr = "192.0.2.1".to_string();
+        assert!(matches!(
+            sniff.remote_socket_addr(),
+            Err(SniffFieldError::InvalidRemoteAddr(_))
+        ));
+        assert_eq!(sniff.remote_ip().unwrap().to_string(), "192.0.2.1");
+
+        sniff.method = "GET POST".to_string();
+        sniff.header.insert("bad header".to_string(), vec![]);
+        sniff.request_uri = "/a b".to_string();
+        sniff.proto_minor = 1;
+        assert!(matches!(
+            sniff.http_method(),
+            Err(SniffFieldError::InvalidMethod(_))
+        ));
+        assert!(matches!(
+            sniff.header_map(),
+            Err(SniffFieldError::InvalidHeaderName(_))
+        ));
+        assert!(matches!(
+            sniff.uri(),
+            Err(SniffFieldError::InvalidUri { .. })
+        ));
+        assert!(matches!(
+            sniff.http_version(),
+            Err(SniffFieldError::UnsupportedVersion { major: 2, minor: 1 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_sniff_payloads() {
+        async fn extract(body: &'static str, config: Option<SniffConfig>) -> Response {
+            let mut req = Request::builder()
+                .uri("/sniff")
+                .header(REQUEST_ID_HEADER, "abc123")
+                .body(Body::from(body))
+                .unwrap();
+            if let Some(config) = config {
+                req.extensions_mut().insert(config);
+            }
+            DynamicSniffForwardRequest::from_request(req, &())
+                .await
+                .unwrap_err()
+                .into_response()
+        }
+
+        let small = SniffConfig::new().with_body_limit(8);
+        assert_eq!(
+            extract("{\"method\": \"GET\"}", Some(small)).await.status(),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+        assert_eq!(
+            extract("not json", None).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            extract(
+                "not json",
+                Some(small.with_rejection_policy(SniffRejectionPolicy::ControlError))
+            )
+            .await
+            .status(),
+            ControlStatusCode::Error.status_code()
+        );
+
+        let req = Request::builder()
+            .header(REQUEST_ID_HEADER, "abc123")
+            .body(Body::from("{}"))
+            .unwrap();
+        let rejection = DynamicSniffForwardRequest::from_request(req, &())
+            .await
+            .unwrap_err();
+        assert_eq!(rejection.request_id(), Some("abc123"));
+        assert!(matches!(rejection.error(), SniffExtractorError::Json(_)));
+    }
+
+    #[tokio::test]
+    async fn capture_rewrites_path() {
+        #[debug_handler]
+        async fn handler(req: Request<Body>) -> (StatusCode, String) {
+            (StatusCode::OK, req.uri().path().to_string())
+        }
+
+        let capture_service = DynamicCaptureService::new("/d_capture/", handler.into_service());
+
+        let app = Router::<()>::new().nest_service("/d_capture/", capture_service);
+
+        let req = Request::builder()
+            .uri("/d_capture/some/path?query=1")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.oneshot(req).await.unwrap();
+        let body_bytes = body::to_bytes(response.into_body(), 1024).await.unwrap();
+        let body_str = String::from_utf8(body_bytes.to_vec()).unwrap();
+        assert_eq!(body_str, "/some/path");
+    }
+
+    #[tokio::test]
+    async fn capture_serves_stateful_router() {
+        let router = Router::new().route(
+            "/some/path",
+            axum::routing::get(async |State(greeting): State<&'static str>| greeting),
+        );
+        let capture_service = DynamicCaptureService::from_router("/d_capture/", router, "hello");
+        let req = Request::builder()
+            .uri("/d_capture/some/path")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = capture_service.oneshot(req).await.unwrap();
+        let body_bytes = body::to_bytes(response.into_body(), 1024).await.unwrap();
+        assert_eq!(body_bytes, "hello");
+    }
+
+    #[tokio::test]
+    async fn capture_applies_layers() {
+        async fn handler(req: Request<Body>) -> String {
+            req.uri().path().to_string()
+        }
+
+        let capture_service = DynamicCaptureService::new("/d_capture/", handler.into_service())
+            .layer(axum::middleware::map_request(async |req: Request<Body>| {
+                assert_eq!(req.uri().path(), "/some/path");
+                req
+            }))
+            .layer(axum::middleware::map_response(
+                async |mut resp: Response| {
+                    resp.headers_mut()
+                        .insert("x-layer", "capture".parse().unwrap());
+                    resp
+                },
+            ));
+        let req = Request::builder()
+            .uri("/d_capture/some/path")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = capture_service.oneshot(req).await.unwrap();
+        assert_eq!(response.headers()["x-layer"], "capture");
+    }
+
+    #[tokio::test]
+    async fn capture_provides_sniff_data() {
+        async fn handler(
+            SniffData(tenant): SniffData<&'static str>,
+            count: Option<SniffData<u32>>,
+        ) -> String {
+            format!("{tenant} {:?}", count.map(|SniffData(count)| count))
+        }
+
+        let tenants = Arc::new(SniffStore::new(std::time::Duration::from_secs(60)));
+        let counts = Arc::new(SniffStore::<u32>::new(std::time::Duration::from_secs(60)));
+        tenants.insert("abc123", "acme");
+        let capture_service = DynamicCaptureService::new("/d_capture/", handler.into_service())
+            .with_sniff_store(tenants.clone())
+            .with_sniff_store(counts);
+        let capture = || {
+            Request::builder()
+                .uri("/d_capture/some/path")
+                .header(REQUEST_ID_HEADER, "abc123")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = capture_service.clone().oneshot(capture()).await.unwrap();
+        let body_bytes = body::to_bytes(response.into_body(), 1024).await.unwrap();
+        assert_eq!(body_bytes, "acme None");
+
+        // the data is taken by the first capture of the request
+        let response = capture_service.oneshot(capture()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[tokio::test]
+    async fn capture_provides_request_context() {
+        async fn handler(ctx: ZoraxyRequestContext) -> String {
+            format!(
+                "{} {} {:?}",
+                ctx.original_uri(),
+                ctx.ingress_uri(),
+                ctx.request_id()
+            )
+        }
+
+        let capture_service = DynamicCaptureService::new("/d_capture/", handler.into_service());
+        let req = Request::builder()
+            .uri("/d_capture/some/path?query=1")
+            .header(REQUEST_ID_HEADER, "abc123")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = capture_service.oneshot(req).await.unwrap();
+        let body_bytes = body::to_bytes(response.into_body(), 1024).await.unwrap();
+        assert_eq!(
+            body_bytes,
+            r#"/some/path?query=1 /d_capture/some/path?query=1 Some("abc123")"#
+        );
+    }
+}
@@ -0,0 +1,600 @@
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use arc_swap::ArcSwap;
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::response::{IntoResponse, Response};
+use tower::util::BoxCloneSyncService;
+use tower::{Service, ServiceExt};
+use tracing::{debug, warn};
+
+use super::{
+    DynamicCaptureService, REQUEST_ID_HEADER, SniffConfig, SniffDecision, SniffStore,
+    normalize_ingress,
+};
+use crate::metrics::CaptureMetrics;
+
+type BoxedSniffService = BoxCloneSyncService<Request<Body>, Response, Infallible>;
+
+/// How long the router remembers which route claimed a request, by default
+const DEFAULT_CLAIM_TTL: Duration = Duration::from_secs(60);
+
+/// A named sniffer and the capture handler serving the requests it accepts
+#[derive(Clone)]
+struct DynamicRoute {
+    name: String,
+    priority: i32,
+    enabled: bool,
+    sniffer: BoxedSniffService,
+    capture: DynamicCaptureService,
+}
+
+/// Routes ordered by descending priority, routes of equal priority in registration order
+type RouteTable = Vec<DynamicRoute>;
+
+/// Router sharing the single dynamic capture sniff and ingress pair between several routes
+///
+/// Each route is a named sniffer, answering the sniff like a handler for the sniff path would,
+/// and the capture handler for the requests it accepts. A sniff is offered to the enabled routes
+/// by descending priority, the first one accepting it claims the request and its capture
+/// is dispatched to that route's capture handler, by the request id Zoraxy sends with both.
+///
+/// Like `StaticPathRouter`, routes can be registered, removed and toggled while serving
+/// through the `Arc` shared with the services. Both services are advertised as usual with `DynamicCaptureSettings`.
+///
+/// ```
+/// use std::sync::Arc;
+/// use axum::Router;
+/// use axum::handler::HandlerWithoutStateExt;
+/// use zoraxy_rs::prelude::*;
+///
+/// let router = Arc::new(DynamicRouter::new("/d_capture"));
+/// router.register_route(
+///     "api",
+///     10,
+///     SniffRules::new(SniffRule::path_prefix("/api")).unwrap().into_sniff_service(),
+///     (async || "api").into_service(),
+/// );
+/// router.register_route(
+///     "fallback",
+///     0,
+///     (async || SniffDecision::Accept).into_service(),
+///     (async || "fallback").into_service(),
+/// );
+/// router.set_route_enabled("fallback", false);
+///
+/// let app: Router = Router::new()
+///     .route_service("/d_sniff/", router.clone().into_sniff_service())
+///     .nest_service("/d_capture/", router.into_capture_service());
+/// ```
+pub struct DynamicRouter {
+    routes: ArcSwap<RouteTable>,
+    capture_ingress: String,
+    claims: SniffStore<String>,
+    sniff_config: SniffConfig,
+    metrics: Option<Arc<CaptureMetrics>>,
+}
+
+impl DynamicRouter {
+    /// Create a router for the dynamic capture ingress `capture_ingress`
+    #[must_use]
+    pub fn new(capture_ingress: &str) -> Self {
+        Self {
+            routes: ArcSwap::from_pointee(Vec::new()),
+            capture_ingress: normalize_ingress(capture_ingress),
+            claims: SniffStore::new(DEFAULT_CLAIM_TTL),
+            sniff_config: SniffConfig::default(),
+            metrics: None,
+        }
+    }
+
+    /// Set how long the route that claimed a request is remembered, until its capture arrives,
+    /// defaults to 60 seconds
+    #[must_use]
+    pub fn with_claim_ttl(mut self, ttl: Duration) -> Self {
+        self.claims = SniffStore::new(ttl);
+        self
+    }
+
+    /// Set the sniff payload limit and rejection policy, also used by the sniffers' `DynamicSniffForwardRequest` extractors
+    #[must_use]
+    pub const fn with_sniff_config(mut self, config: SniffConfig) -> Self {
+        self.sniff_config = config;
+        self
+    }
+
+    /// Count the sniff decisions of the router in `metrics`
+    ///
+    /// Capture latency is recorded per route, by registering a `DynamicCaptureService` with metrics
+    /// through [`Self::register_capture_service`].
+    #[must_use]
+    pub fn with_metrics(mut self, metrics: Arc<CaptureMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Register a route, replacing the route previously registered under the same name
+    ///
+    /// `sniffer` is called with the sniff request and accepts it by responding with `200 OK`,
+    /// e.g. with a [`SniffDecision`], [`SniffRules::into_sniff_service`](super::SniffRules::into_sniff_service)
+    /// or a handler using the `DynamicSniffForwardRequest` extractor.
+    /// `501 Not Implemented` passes the sniff on to the next route, any other response, e.g. an extractor
+    /// rejection or a [`ControlStatusCode::Error`](crate::ControlStatusCode::Error), is returned to Zoraxy as is.
+    /// `handler` serves the captured requests like it would in a `DynamicCaptureService`.
+    pub fn register_route<Sn, H>(
+        &self,
+        name: impl Into<String>,
+        priority: i32,
+        sniffer: Sn,
+        handler: H,
+    ) where
+        Sn: Service<Request<Body>, Response = Response, Error = Infallible>
+            + Send
+            + Sync
+            + Clone
+            + 'static,
+        Sn::Future: Send + 'static,
+        H: Service<Request<Body>, Response = Response, Error = Infallible>
+            + Send
+            + Sync
+            + Clone
+            + 'static,
+        H::Future: Send + 'static,
+    {
+        let capture = DynamicCaptureService::new(&self.capture_ingress, handler);
+        self.register_capture_service(name, priority, sniffer, capture);
+    }
+
+    /// Register a route serving its captures with a configured `DynamicCaptureService`,
+    /// e.g. one with layers, metrics or sniff stores
+    ///
+    /// The capture service's ingress is replaced by the router's.
+    pub fn register_capture_service<Sn>(
+        &self,
+        name: impl Into<String>,
+        priority: i32,
+        sniffer: Sn,
+        mut capture: DynamicCaptureService,
+    ) where
+        Sn: Service<Request<Body>, Response = Response, Error = Infallible>
+            + Send
+            + Sync
+            + Clone
+            + 'static,
+        Sn::Future: Send + 'static,
+    {
+        capture.ingress.clone_from(&self.capture_ingress);
+        let route = DynamicRoute {
+            name: name.into(),
+            priority,
+            enabled: true,
+            sniffer: BoxCloneSyncService::new(sniffer),
+            capture,
+        };
+        self.update_routes(|routes| {
+            routes.retain(|registered| registered.name != route.name);
+            let index = routes.partition_point(|registered| registered.priority >= route.priority);
+            routes.insert(index, route.clone());
+        });
+    }
+
+    /// Remove the route registered under `name`
+    pub fn remove_route(&self, name: &str) {
+        self.update_routes(|routes| routes.retain(|route| route.name != name));
+    }
+
+    /// Enable or disable the route registered under `name`, returns `false` if there is none
+    ///
+    /// Disabled routes are not offered sniffs, captures of requests they already claimed are still served.
+    pub fn set_route_enabled(&self, name: &str, enabled: bool) -> bool {
+        let mut found = false;
+        self.update_routes(|routes| {
+            found = false;
+            for route in routes.iter_mut().filter(|route| route.name == name) {
+                route.enabled = enabled;
+                found = true;
+            }
+        });
+        found
+    }
+
+    /// Whether the route registered under `name` is enabled, `None` if there is none
+    #[must_use]
+    pub fn is_route_enabled(&self, name: &str) -> Option<bool> {
+        self.routes
+            .load()
+            .iter()
+            .find(|route| route.name == name)
+            .map(|route| route.enabled)
+    }
+
+    /// Names of the registered routes, in the order sniffs are offered to them
+    #[must_use]
+    pub fn route_names(&self) -> Vec<String> {
+        self.routes
+            .load()
+            .iter()
+            .map(|route| route.name.clone())
+            .collect()
+    }
+
+    /// Swap in a modified copy of the route table, `update` may be called again if another update raced it
+    fn update_routes(&self, mut update: impl FnMut(&mut RouteTable)) {
+        self.routes.rcu(|routes| {
+            let mut routes = RouteTable::clone(routes);
+            update(&mut routes);
+            routes
+        });
+    }
+
+    pub(crate) async fn dispatch_sniff(&self, req: Request<Body>) -> Result<Response, Infallible> {
+        let resp = self.offer_sniff(req).await?;
+        if let Some(metrics) = &self.metrics {
+            metrics.record_sniff(resp.status());
+        }
+        Ok(resp)
+    }
+
+    /// Offer the sniff to the enabled routes, the response is the router's decision
+    async fn offer_sniff(&self, req: Request<Body>) -> Result<Response, Infallible> {
+        let (mut parts, body) = req.into_parts();
+        let Some(request_id) = parts
+            .headers
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(ToString::to_string)
+        else {
+            warn!(target: "zoraxy::dynamic_router", "sniff request has no request id, skipping it");
+            return Ok(SniffDecision::Skip.into_response());
+        };
+        let bytes = match self.sniff_config.read_body(body, Some(&request_id)).await {
+            Ok(bytes) => bytes,
+            Err(rejection) => return Ok(rejection.into_response()),
+        };
+        parts.extensions.insert(self.sniff_config);
+
+        let routes = self.routes.load_full();
+        for route in routes.iter().filter(|route| route.enabled) {
+            let req = Request::from_parts(parts.clone(), Body::from(bytes.clone()));
+            let resp = route.sniffer.clone().oneshot(req).await?;
+            match resp.status() {
+                StatusCode::OK => {
+                    debug!(target: "zoraxy::dynamic_router", route = %route.name, request_id, "Route claimed request");
+                    self.claims.insert(request_id, route.name.clone());
+                    return Ok(SniffDecision::Accept.into_response());
+                }
+                StatusCode::NOT_IMPLEMENTED => {}
+                status => {
+                    warn!(
+                        target: "zoraxy::dynamic_router",
+                        route = %route.name,
+                        request_id,
+                        %status,
+                        "Sniffer failed, returning its response"
+                    );
+                    return Ok(resp);
+                }
+            }
+        }
+        Ok(SniffDecision::Skip.into_response())
+    }
+
+    pub(crate) async fn dispatch_capture(
+        &self,
+        req: Request<Body>,
+    ) -> Result<Response, Infallible> {
+        let claimed_by = req
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|request_id| self.claims.take(request_id));
+        let capture = claimed_by.as_ref().and_then(|name| {
+            self.routes
+                .load()
+                .iter()
+                .find(|route| route.name == *name)
+                .map(|route| route.capture.clone())
+        });
+
+        if let Some(capture) = capture {
+            return capture.oneshot(req).await;
+        }
+        warn!(
+            target: "zoraxy::dynamic_router",
+            route = claimed_by,
+            "no route for captured request, its claim expired or the route was removed"
+        );
+        Ok((
+            StatusCode::NOT_FOUND,
+            "No dynamic capture route claimed this request",
+        )
+            .into_response())
+    }
+
+    /// Create the service for the dynamic capture sniff path, offering each sniff to the enabled routes
+    pub const fn into_sniff_service(self: Arc<Self>) -> DynamicRouterSniffService {
+        DynamicRouterSniffService { router: self }
+    }
+
+    /// Create the service for the dynamic capture ingress, serving each capture with the route that claimed it
+    pub const fn into_capture_service(self: Arc<Self>) -> DynamicRouterCaptureService {
+        DynamicRouterCaptureService { router: self }
+    }
+}
+
+/// Service for the dynamic capture sniff path of a [`DynamicRouter`]
+#[derive(Clone)]
+pub struct DynamicRouterSniffService {
+    router: Arc<DynamicRouter>,
+}
+
+impl Service<Request<Body>> for DynamicRouterSniffService {
+    type Response = Response;
+    type Error = Infallible;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let router = self.router.clone();
+        Box::pin(async move { router.dispatch_sniff(req).await })
+    }
+}
+
+/// Service for the dynamic capture ingress of a [`DynamicRouter`]
+#[derive(Clone)]
+pub struct DynamicRouterCaptureService {
+    router: Arc<DynamicRouter>,
+}
+
+impl Service<Request<Body>> for DynamicRouterCaptureService {
+    type Response = Response;
+    type Error = Infallible;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let router = self.router.clone();
+        Box::pin(async move { router.dispatch_capture(req).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamic_router::{
+        DynamicSniffForwardRequest, SniffRejectionPolicy, SniffRule, SniffRules,
+    };
+    use crate::types::ControlStatusCode;
+    use axum::body;
+    use axum::handler::HandlerWithoutStateExt;
+    use serde_json::json;
+
+    fn sniff_request(request_id: &str, request_uri: &str) -> Request<Body> {
+        let body = json!({
+            "method": "GET",
+            "hostname": "example.com",
+            "url": request_uri,
+            "header": {},
+            "remote_addr": "127.0.0.1:8080",
+            "host": "example.com",
+            "request_uri": request_uri,
+            "proto": "HTTP/1.1",
+            "proto_major": 1,
+            "proto_minor": 1
+        });
+        Request::builder()
+            .uri("/d_sniff/")
+            .header(REQUEST_ID_HEADER, request_id)
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    async fn capture(router: &DynamicRouter, request_id: &str) -> (StatusCode, String) {
+        let req = Request::builder()
+            .uri("/d_capture/some/path")
+            .header(REQUEST_ID_HEADER, request_id)
+            .body(Body::empty())
+            .unwrap();
+        let resp = router.dispatch_capture(req).await.unwrap();
+        let status = resp.status();
+        let body = body::to_bytes(resp.into_body(), 1024).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    fn path_prefix(prefix: &str) -> BoxedSniffService {
+        SniffRules::new(SniffRule::path_prefix(prefix))
+            .unwrap()
+            .into_sniff_service()
+    }
+
+    #[tokio::test]
+    async fn dispatches_captures_to_the_claiming_route() {
+        let router = DynamicRouter::new("/d_capture");
+        router.register_route(
+            "catch_all",
+            0,
+            (async || SniffDecision::Accept).into_service(),
+            (async || "catch all").into_service(),
+        );
+        router.register_route(
+            "api",
+            10,
+            path_prefix("/api"),
+            (async |req: Request<Body>| format!("api {}", req.uri())).into_service(),
+        );
+        router.register_route(
+            "api_v2",
+            10,
+            path_prefix("/api/v2"),
+            (async || "api v2").into_service(),
+        );
+        assert_eq!(router.route_names(), ["api", "api_v2", "catch_all"]);
+
+        for (request_id, request_uri) in [("1", "/api/v2/users"), ("2", "/other")] {
+            let resp = router
+                .dispatch_sniff(sniff_request(request_id, request_uri))
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+        assert_eq!(
+            capture(&router, "1").await,
+            (StatusCode::OK, "api /some/path".to_string())
+        );
+        assert_eq!(
+            capture(&router, "2").await,
+            (StatusCode::OK, "catch all".to_string())
+        );
+        assert_eq!(capture(&router, "1").await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn skips_disabled_routes() {
+        let router = DynamicRouter::new("/d_capture");
+        router.register_route(
+            "api",
+            0,
+            path_prefix("/api"),
+            (async || "api").into_service(),
+        );
+
+        assert!(router.set_route_enabled("api", false));
+        assert!(!router.set_route_enabled("missing", false));
+        assert_eq!(router.is_route_enabled("api"), Some(false));
+        let resp = router
+            .dispatch_sniff(sniff_request("1", "/api"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_IMPLEMENTED);
+
+        router.set_route_enabled("api", true);
+        let resp = router
+            .dispatch_sniff(sniff_request("2", "/api"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        router.remove_route("api");
+        assert_eq!(capture(&router, "2").await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn records_sniff_metrics() {
+        let metrics = Arc::new(CaptureMetrics::new());
+        let router = DynamicRouter::new("/d_capture").with_metrics(metrics.clone());
+        router.register_route(
+            "api",
+            0,
+            path_prefix("/api"),
+            (async || "api").into_service(),
+        );
+
+        for (request_id, request_uri) in [("1", "/api"), ("2", "/api/users"), ("3", "/other")] {
+            router
+                .dispatch_sniff(sniff_request(request_id, request_uri))
+                .await
+                .unwrap();
+        }
+
+        let rendered = metrics.render();
+        for line in [
+            r#"zoraxy_sniff_total{decision="accept"} 2"#,
+            r#"zoraxy_sniff_total{decision="skip"} 1"#,
+        ] {
+            assert!(
+                rendered.lines().any(|l| l == line),
+                "{line} missing from\n{rendered}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_oversized_sniffs() {
+        let router = DynamicRouter::new("/d_capture").with_sniff_config(
+            SniffConfig::new()
+                .with_body_limit(16)
+                .with_rejection_policy(SniffRejectionPolicy::ControlError),
+        );
+        router.register_route(
+            "any",
+            0,
+            (async || SniffDecision::Accept).into_service(),
+            (async || "any").into_service(),
+        );
+
+        let resp = router
+            .dispatch_sniff(sniff_request("1", "/"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status().as_u16(), 580);
+    }
+
+    #[tokio::test]
+    async fn returns_failed_sniffer_responses() {
+        let router = DynamicRouter::new("/d_capture");
+        router.register_route(
+            "broken",
+            1,
+            (async || ControlStatusCode::Error).into_service(),
+            (async || "broken").into_service(),
+        );
+        router.register_route(
+            "any",
+            0,
+            (async || SniffDecision::Accept).into_service(),
+            (async || "any").into_service(),
+        );
+
+        let resp = router
+            .dispatch_sniff(sniff_request("1", "/"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status().as_u16(), 580);
+        assert_eq!(capture(&router, "1").await.0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn passes_the_sniff_to_every_sniffer() {
+        let router = DynamicRouter::new("/d_capture");
+        router.register_route(
+            "first",
+            1,
+            (async |sniff: DynamicSniffForwardRequest| {
+                assert_eq!(sniff.request_uuid(), Some("1"));
+                SniffDecision::Skip
+            })
+            .into_service(),
+            (async || "first").into_service(),
+        );
+        router.register_route(
+            "second",
+            0,
+            (async |sniff: DynamicSniffForwardRequest| {
+                assert_eq!(sniff.request_uri, "/x");
+                SniffDecision::Accept
+            })
+            .into_service(),
+            (async || "second").into_service(),
+        );
+
+        let resp = router
+            .dispatch_sniff(sniff_request("1", "/x"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(capture(&router, "1").await.1, "second");
+    }
+}
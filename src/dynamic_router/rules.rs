@@ -0,0 +1,469 @@
+use std::convert::Infallible;
+use std::net::IpAddr;
+
+use axum::Extension;
+use axum::body::Body;
+use axum::handler::HandlerWithoutStateExt;
+use axum::http::{Method, Request};
+use axum::response::Response;
+use globset::{Glob, GlobMatcher};
+use ipnet::IpNet;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tower::Layer;
+use tower::util::BoxCloneSyncService;
+
+use super::{DynamicSniffForwardRequest, SniffConfig, SniffDecision};
+
+/// A declarative matcher on the fields of a [`DynamicSniffForwardRequest`], compiled into [`SniffRules`]
+///
+/// Rules can be built in code or loaded from JSON, where every rule is an object with a single key:
+///
+/// ```json
+/// {"all": [
+///     {"hostname": "*.example.com"},
+///     {"any": [{"path_prefix": "/api"}, {"path_regex": "^/v[0-9]+/"}]},
+///     {"not": {"method": ["DELETE"]}},
+///     {"header": {"name": "Authorization"}},
+///     {"remote_addr": ["10.0.0.0/8", "fd00::/8"]},
+///     {"proto": "HTTP/2"}
+/// ]}
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SniffRule {
+    /// Matches if all rules match, or if there are none
+    All(Vec<Self>),
+    /// Matches if any of the rules matches
+    Any(Vec<Self>),
+    /// Matches if the rule doesn't match, see also `!rule`
+    Not(Box<Self>),
+    /// Matches the hostname, without port, against a case-insensitive glob such as `*.example.com`
+    Hostname(String),
+    /// Matches if the request path starts with the prefix, as a plain string:
+    /// `/api` also matches `/apiv2`, use `/api/` or a `PathRegex` such as `^/api(/|$)` to match whole segments
+    PathPrefix(String),
+    /// Matches the request path against a regular expression
+    PathRegex(String),
+    /// Matches any of the methods
+    Method(Vec<String>),
+    /// Matches if the header is present, and if a value regex is given, if any of its values matches it
+    Header {
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        value: Option<String>,
+    },
+    /// Matches if the remote address is in any of the CIDR ranges, single addresses are allowed too
+    RemoteAddr(Vec<String>),
+    /// Matches the protocol version, e.g. `HTTP/1.1` or `HTTP/2`, a missing minor version matches any
+    Proto(String),
+}
+
+impl SniffRule {
+    /// A rule matching if all `rules` match
+    #[must_use]
+    pub fn all(rules: impl IntoIterator<Item = Self>) -> Self {
+        Self::All(rules.into_iter().collect())
+    }
+
+    /// A rule matching if any of `rules` matches
+    #[must_use]
+    pub fn any(rules: impl IntoIterator<Item = Self>) -> Self {
+        Self::Any(rules.into_iter().collect())
+    }
+
+    /// A rule matching hostnames against a case-insensitive glob such as `*.example.com`
+    #[must_use]
+    pub fn hostname(glob: impl Into<String>) -> Self {
+        Self::Hostname(glob.into())
+    }
+
+    /// A rule matching request paths starting with `prefix`, compared as a plain string, see [`Self::PathPrefix`]
+    #[must_use]
+    pub fn path_prefix(prefix: impl Into<String>) -> Self {
+        Self::PathPrefix(prefix.into())
+    }
+
+    /// A rule matching request paths against the regular expression `regex`
+    #[must_use]
+    pub fn path_regex(regex: impl Into<String>) -> Self {
+        Self::PathRegex(regex.into())
+    }
+
+    /// A rule matching any of `methods`
+    #[must_use]
+    pub fn method(methods: impl IntoIterator<Item = Method>) -> Self {
+        Self::Method(methods.into_iter().map(|m| m.to_string()).collect())
+    }
+
+    /// A rule matching requests with the header `name`
+    #[must_use]
+    pub fn header_present(name: impl Into<String>) -> Self {
+        Self::Header {
+            name: name.into(),
+            value: None,
+        }
+    }
+
+    /// A rule matching requests with a value of the header `name` matching `regex`
+    #[must_use]
+    pub fn header_value(name: impl Into<String>, regex: impl Into<String>) -> Self {
+        Self::Header {
+            name: name.into(),
+            value: Some(regex.into()),
+        }
+    }
+
+    /// A rule matching remote addresses in any of the CIDR ranges or single addresses `cidrs`
+    #[must_use]
+    pub fn remote_addr(cidrs: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self::RemoteAddr(cidrs.into_iter().map(Into::into).collect())
+    }
+
+    /// A rule matching the protocol version `proto`, e.g. `HTTP/1.1` or `HTTP/2`
+    #[must_use]
+    pub fn proto(proto: impl Into<String>) -> Self {
+        Self::Proto(proto.into())
+    }
+}
+
+/// A rule matching if `self` doesn't match
+impl std::ops::Not for SniffRule {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Self::Not(Box::new(self))
+    }
+}
+
+/// Errors returned when compiling or loading [`SniffRule`]s
+#[derive(Debug, thiserror::Error)]
+pub enum SniffRuleError {
+    /// The rules JSON is malformed
+    #[error("invalid sniff rules JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    /// A `hostname` rule has an invalid glob
+    #[error("invalid hostname glob {glob:?}: {source}")]
+    InvalidGlob {
+        glob: String,
+        source: globset::Error,
+    },
+    /// A `path_regex` or `header` rule has an invalid regular expression
+    #[error("invalid regex {regex:?}: {source}")]
+    InvalidRegex { regex: String, source: regex::Error },
+    /// A `method` rule has an invalid method
+    #[error("invalid method {0:?}")]
+    InvalidMethod(String),
+    /// A `remote_addr` rule has an invalid CIDR range or address
+    #[error("invalid CIDR range {cidr:?}")]
+    InvalidCidr { cidr: String },
+    /// A `proto` rule has an invalid protocol version
+    #[error("invalid protocol {0:?}, expected e.g. HTTP/1.1")]
+    InvalidProto(String),
+}
+
+/// Compiled [`SniffRule`]s, accepting the sniffed requests they match
+///
+/// ```
+/// use zoraxy_rs::prelude::*;
+///
+/// let rules = SniffRules::new(SniffRule::all([
+///     SniffRule::hostname("*.example.com"),
+///     SniffRule::path_prefix("/foobar"),
+/// ]))
+/// .unwrap();
+/// let sniff_service = rules.into_sniff_service();
+/// ```
+#[derive(Debug, Clone)]
+pub struct SniffRules {
+    matcher: Matcher,
+    sniff_config: Option<SniffConfig>,
+}
+
+impl SniffRules {
+    /// Compile the rule
+    ///
+    /// # Errors
+    /// * Returns an error if a glob, regex, method, CIDR range or protocol in the rule is invalid
+    pub fn new(rule: SniffRule) -> Result<Self, SniffRuleError> {
+        Ok(Self {
+            matcher: Matcher::compile(rule)?,
+            sniff_config: None,
+        })
+    }
+
+    /// Set the sniff payload limit and rejection policy of the sniff service,
+    /// defaults to the config of the `DynamicRouter` the service is registered on, if any
+    #[must_use]
+    pub const fn with_sniff_config(mut self, config: SniffConfig) -> Self {
+        self.sniff_config = Some(config);
+        self
+    }
+
+    /// Load and compile a rule from JSON, see [`SniffRule`] for the format
+    ///
+    /// # Errors
+    /// * Returns an error if the JSON is not a valid rule, or the rule doesn't compile
+    pub fn from_json(json: &str) -> Result<Self, SniffRuleError> {
+        Self::new(serde_json::from_str(json)?)
+    }
+
+    /// Whether the rules match the sniffed request
+    #[must_use]
+    pub fn matches(&self, sniff: &DynamicSniffForwardRequest) -> bool {
+        self.matcher.matches(sniff)
+    }
+
+    /// Accept the sniffed request if the rules match it, skip it otherwise
+    #[must_use]
+    pub fn decide(&self, sniff: &DynamicSniffForwardRequest) -> SniffDecision {
+        if self.matches(sniff) {
+            SniffDecision::Accept
+        } else {
+            SniffDecision::Skip
+        }
+    }
+
+    /// A service for the dynamic capture sniff path, accepting the requests the rules match
+    #[must_use]
+    pub fn into_sniff_service(self) -> BoxCloneSyncService<Request<Body>, Response, Infallible> {
+        let sniff_config = self.sniff_config;
+        let handler = async move |sniff: DynamicSniffForwardRequest| self.decide(&sniff);
+        match sniff_config {
+            Some(config) => {
+                BoxCloneSyncService::new(Extension(config).layer(handler.into_service()))
+            }
+            None => BoxCloneSyncService::new(handler.into_service()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Matcher {
+    All(Vec<Self>),
+    Any(Vec<Self>),
+    Not(Box<Self>),
+    Hostname(GlobMatcher),
+    PathPrefix(String),
+    PathRegex(Regex),
+    Method(Vec<Method>),
+    Header { name: String, value: Option<Regex> },
+    RemoteAddr(Vec<IpNet>),
+    Proto { major: i32, minor: Option<i32> },
+}
+
+impl Matcher {
+    fn compile(rule: SniffRule) -> Result<Self, SniffRuleError> {
+        let compile_all = |rules: Vec<SniffRule>| -> Result<Vec<_>, _> {
+            rules.into_iter().map(Self::compile).collect()
+        };
+        let regex = |regex: String| {
+            Regex::new(&regex).map_err(|source| SniffRuleError::InvalidRegex { regex, source })
+        };
+
+        Ok(match rule {
+            SniffRule::All(rules) => Self::All(compile_all(rules)?),
+            SniffRule::Any(rules) => Self::Any(compile_all(rules)?),
+            SniffRule::Not(rule) => Self::Not(Box::new(Self::compile(*rule)?)),
+            SniffRule::Hostname(glob) => Self::Hostname(
+                globset::GlobBuilder::new(&glob)
+                    .case_insensitive(true)
+                    .build()
+                    .map(|compiled: Glob| compiled.compile_matcher())
+                    .map_err(|source| SniffRuleError::InvalidGlob { glob, source })?,
+            ),
+            SniffRule::PathPrefix(prefix) => Self::PathPrefix(prefix),
+            SniffRule::PathRegex(path_regex) => Self::PathRegex(regex(path_regex)?),
+            SniffRule::Method(methods) => Self::Method(
+                methods
+                    .into_iter()
+                    .map(|method| {
+                        Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+                            .map_err(|_| SniffRuleError::InvalidMethod(method))
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            SniffRule::Header { name, value } => Self::Header {
+                name,
+                value: value.map(regex).transpose()?,
+            },
+            SniffRule::RemoteAddr(cidrs) => Self::RemoteAddr(
+                cidrs
+                    .into_iter()
+                    .map(|cidr| {
+                        cidr.parse::<IpNet>()
+                            .or_else(|_| cidr.parse::<IpAddr>().map(IpNet::from))
+                            .map_err(|_| SniffRuleError::InvalidCidr { cidr })
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            SniffRule::Proto(proto) => {
+                let invalid = || SniffRuleError::InvalidProto(proto.clone());
+                let version = proto
+                    .get(..5)
+                    .filter(|scheme| scheme.eq_ignore_ascii_case("HTTP/"))
+                    .map(|_| &proto[5..])
+                    .ok_or_else(invalid)?;
+                let (major, minor) = match version.split_once('.') {
+                    Some((major, minor)) => (major, Some(minor)),
+                    None => (version, None),
+                };
+                Self::Proto {
+                    major: major.parse().map_err(|_| invalid())?,
+                    minor: minor.map(str::parse).transpose().map_err(|_| invalid())?,
+                }
+            }
+        })
+    }
+
+    fn matches(&self, sniff: &DynamicSniffForwardRequest) -> bool {
+        match self {
+            Self::All(matchers) => matchers.iter().all(|m| m.matches(sniff)),
+            Self::Any(matchers) => matchers.iter().any(|m| m.matches(sniff)),
+            Self::Not(matcher) => !matcher.matches(sniff),
+            Self::Hostname(glob) => glob.is_match(strip_port(&sniff.hostname)),
+            Self::PathPrefix(prefix) => request_path(sniff).starts_with(prefix.as_str()),
+            Self::PathRegex(regex) => regex.is_match(request_path(sniff)),
+            Self::Method(methods) => methods
+                .iter()
+                .any(|method| method.as_str().eq_ignore_ascii_case(&sniff.method)),
+            Self::Header { name, value } => sniff
+                .header
+                .iter()
+                .filter(|(header, _)| header.eq_ignore_ascii_case(name))
+                .any(|(_, values)| {
+                    value
+                        .as_ref()
+                        .is_none_or(|regex| values.iter().any(|v| regex.is_match(v)))
+                }),
+            Self::RemoteAddr(networks) => sniff
+                .remote_ip()
+                .ok()
+                .is_some_and(|ip| networks.iter().any(|network| network.contains(&ip))),
+            Self::Proto { major, minor } => {
+                sniff.proto_major == *major && minor.is_none_or(|minor| sniff.proto_minor == minor)
+            }
+        }
+    }
+}
+
+fn request_path(sniff: &DynamicSniffForwardRequest) -> &str {
+    sniff
+        .request_uri
+        .split_once('?')
+        .map_or(sniff.request_uri.as_str(), |(path, _)| path)
+}
+
+fn strip_port(host: &str) -> &str {
+    match host.rsplit_once(':') {
+        Some((name, port)) if !name.contains(':') && port.bytes().all(|b| b.is_ascii_digit()) => {
+            name
+        }
+        _ => host,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamic_router::SniffRejectionPolicy;
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    fn sniff() -> DynamicSniffForwardRequest {
+        serde_json::from_value(json!({
+            "method": "POST",
+            "hostname": "api.example.com:8443",
+            "url": "/foobar/items?x=1",
+            "header": {"Authorization": ["Bearer abc"], "X-Tenant": ["42"]},
+            "remote_addr": "[fd00::1]:51234",
+            "host": "api.example.com:8443",
+            "request_uri": "/foobar/items?x=1",
+            "proto": "HTTP/2.0",
+            "proto_major": 2,
+            "proto_minor": 0
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn matches_individual_rules() {
+        let sniff = sniff();
+        let matches = |rule| SniffRules::new(rule).unwrap().matches(&sniff);
+
+        assert!(matches(SniffRule::hostname("*.EXAMPLE.com")));
+        assert!(!matches(SniffRule::hostname("example.com")));
+        assert!(matches(SniffRule::path_prefix("/foobar")));
+        assert!(!matches(SniffRule::path_prefix("/foobar/items?")));
+        assert!(matches(SniffRule::path_regex("^/foobar/[a-z]+$")));
+        assert!(matches(SniffRule::method([Method::GET, Method::POST])));
+        assert!(!matches(SniffRule::method([Method::GET])));
+        assert!(matches(SniffRule::header_present("authorization")));
+        assert!(matches(SniffRule::header_value("x-tenant", "^[0-9]+$")));
+        assert!(!matches(SniffRule::header_value("x-tenant", "^abc$")));
+        assert!(!matches(SniffRule::header_present("cookie")));
+        assert!(matches(SniffRule::remote_addr(["10.0.0.0/8", "fd00::/8"])));
+        assert!(!matches(SniffRule::remote_addr(["10.0.0.0/8"])));
+        assert!(matches(SniffRule::proto("HTTP/2")));
+        assert!(!matches(SniffRule::proto("HTTP/1.1")));
+    }
+
+    #[test]
+    fn combines_rules() {
+        let sniff = sniff();
+        let matches = |rule| SniffRules::new(rule).unwrap().matches(&sniff);
+
+        assert!(matches(SniffRule::all([])));
+        assert!(!matches(SniffRule::any([])));
+        assert!(matches(SniffRule::all([
+            SniffRule::path_prefix("/foobar"),
+            !SniffRule::method([Method::DELETE]),
+            SniffRule::any([SniffRule::proto("HTTP/1.1"), SniffRule::proto("HTTP/2.0")]),
+        ])));
+    }
+
+    #[test]
+    fn loads_rules_from_json() {
+        let rules = SniffRules::from_json(
+            r#"{"all": [
+                {"hostname": "*.example.com"},
+                {"not": {"method": ["delete"]}},
+                {"header": {"name": "X-Tenant", "value": "^4"}}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(rules.decide(&sniff()), SniffDecision::Accept);
+
+        assert!(matches!(
+            SniffRules::from_json(r#"{"path_regex": "("}"#),
+            Err(SniffRuleError::InvalidRegex { .. })
+        ));
+        assert!(matches!(
+            SniffRules::from_json(r#"{"remote_addr": ["10.0.0.0/33"]}"#),
+            Err(SniffRuleError::InvalidCidr { .. })
+        ));
+        assert!(matches!(
+            SniffRules::from_json(r#"{"path": "/"}"#),
+            Err(SniffRuleError::Json(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn applies_sniff_config() {
+        let service = SniffRules::new(SniffRule::all([]))
+            .unwrap()
+            .with_sniff_config(
+                SniffConfig::new()
+                    .with_body_limit(16)
+                    .with_rejection_policy(SniffRejectionPolicy::ControlError),
+            )
+            .into_sniff_service();
+
+        let req = Request::builder()
+            .uri("/d_sniff/")
+            .body(Body::from(serde_json::to_string(&sniff()).unwrap()))
+            .unwrap();
+        let resp = service.oneshot(req).await.unwrap();
+        assert_eq!(resp.status().as_u16(), 580);
+    }
+}
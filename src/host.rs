@@ -0,0 +1,542 @@
+//! A mock Zoraxy host, drives a plugin binary through the host side of the plugin protocol.
+//!
+//! Used by the `zoraxy-plugin-host` binary to test plugins end-to-end without a Zoraxy instance.
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::process::{ExitStatus, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::body::{Body, Bytes};
+use axum::http::{HeaderMap, Request, StatusCode, header};
+use http_body_util::BodyExt;
+use tokio::process::{Child, Command};
+use tracing::{debug, warn};
+
+use crate::dynamic_router::REQUEST_ID_HEADER;
+use crate::spec::{DEV_ZORAXY_VERSION, free_port};
+use crate::static_router::{CAPTURE_HEADER, ORIGINAL_URI_HEADER};
+use crate::types::{ConfigureSpec, Event, IntroSpect, IntroSpectValidationError, RuntimeConstants};
+
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Headers that only apply to a single connection, and aren't forwarded between the client, host and plugin
+const HOP_BY_HOP_HEADERS: [header::HeaderName; 6] = [
+    header::CONNECTION,
+    header::PROXY_AUTHENTICATE,
+    header::TE,
+    header::TRAILER,
+    header::TRANSFER_ENCODING,
+    header::UPGRADE,
+];
+
+/// Errors returned by the [`PluginHost`]
+#[derive(Debug, thiserror::Error)]
+pub enum PluginHostError {
+    /// The plugin binary could not be executed
+    #[error("failed to execute plugin: {0}")]
+    Spawn(#[source] std::io::Error),
+    /// The plugin exited with an error when asked to introspect
+    #[error("plugin exited with {status} when introspecting: {stderr}")]
+    IntrospectFailed { status: ExitStatus, stderr: String },
+    /// The plugin printed something that isn't a valid `IntroSpect`
+    #[error("plugin printed an invalid introspect payload: {0}")]
+    MalformedIntroSpect(#[source] serde_json::Error),
+    /// The plugin's `IntroSpect` failed validation
+    #[error(transparent)]
+    InvalidIntroSpect(#[from] IntroSpectValidationError),
+    /// No free port could be found for the plugin to listen on
+    #[error("failed to find a free port: {0}")]
+    NoFreePort(#[source] std::io::Error),
+    /// The plugin did not start listening on its port in time
+    #[error("plugin did not start listening on port {0} in time")]
+    NotReady(u16),
+    /// The plugin does not declare the settings needed for the operation
+    #[error("plugin does not declare {0}")]
+    NotDeclared(&'static str),
+    /// A payload for the plugin could not be serialized
+    #[error("failed to serialize {0}: {1}")]
+    Serialize(&'static str, #[source] serde_json::Error),
+    /// The body of a request routed through the host could not be read
+    #[error("failed to read request body: {0}")]
+    ReadBody(#[source] axum::Error),
+    /// A request to the plugin failed
+    #[error("request to plugin failed: {0}")]
+    Request(#[from] reqwest::Error),
+    /// Waiting for the plugin to exit failed
+    #[error("failed to wait for plugin to exit: {0}")]
+    Wait(#[source] std::io::Error),
+}
+
+/// How the host routed a request to the plugin
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Capture {
+    /// Matched a static capture path, forwarded to the static capture ingress
+    Static(String),
+    /// Accepted by the plugin's sniff, forwarded to the dynamic capture ingress
+    Dynamic,
+    /// Not captured by the plugin, Zoraxy would process the request itself
+    NotCaptured,
+}
+
+/// The response to a request routed through the host
+#[derive(Debug, Clone)]
+pub struct HostResponse {
+    pub capture: Capture,
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl HostResponse {
+    async fn from_plugin(
+        capture: Capture,
+        resp: reqwest::Response,
+    ) -> Result<Self, PluginHostError> {
+        let status = resp.status();
+        let mut headers = resp.headers().clone();
+        for name in HOP_BY_HOP_HEADERS {
+            headers.remove(name);
+        }
+        Ok(Self {
+            capture,
+            status,
+            headers,
+            body: resp.bytes().await?,
+        })
+    }
+}
+
+impl axum::response::IntoResponse for HostResponse {
+    fn into_response(self) -> axum::response::Response {
+        (self.status, self.headers, self.body).into_response()
+    }
+}
+
+/// A plugin process started by the host
+pub struct PluginHost {
+    child: Child,
+    intro_spect: IntroSpect,
+    configure_spec: ConfigureSpec,
+    http: reqwest::Client,
+    request_counter: AtomicU64,
+}
+
+impl PluginHost {
+    /// Run the plugin with `-introspect`, validate the result,
+    /// then start it with `-configure` and wait for it to listen on its port
+    ///
+    /// # Errors
+    /// * Returns an error if any stage of the handshake fails
+    pub async fn launch<P: AsRef<OsStr>>(program: P) -> Result<Self, PluginHostError> {
+        let output = Command::new(&program)
+            .arg("-introspect")
+            .stderr(Stdio::piped())
+            .output()
+            .await
+            .map_err(PluginHostError::Spawn)?;
+        if !output.status.success() {
+            return Err(PluginHostError::IntrospectFailed {
+                status: output.status,
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            });
+        }
+        let intro_spect: IntroSpect =
+            serde_json::from_slice(&output.stdout).map_err(PluginHostError::MalformedIntroSpect)?;
+        intro_spect.validate()?;
+
+        let configure_spec = ConfigureSpec {
+            port: free_port().map_err(PluginHostError::NoFreePort)?,
+            runtime_constants: RuntimeConstants {
+                zoraxy_version: DEV_ZORAXY_VERSION.to_string(),
+                zoraxy_uuid: "00000000-0000-0000-0000-000000000000".to_string(),
+                development_build: true,
+            },
+            api_key: None,
+            zoraxy_port: None,
+        };
+        let configure_json = serde_json::to_string(&configure_spec)
+            .map_err(|err| PluginHostError::Serialize("configure spec", err))?;
+
+        let child = Command::new(&program)
+            .arg("-configure")
+            .arg(configure_json)
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(PluginHostError::Spawn)?;
+
+        let host = Self {
+            child,
+            intro_spect,
+            configure_spec,
+            http: reqwest::Client::new(),
+            request_counter: AtomicU64::new(0),
+        };
+        host.wait_until_ready().await?;
+        Ok(host)
+    }
+
+    async fn wait_until_ready(&self) -> Result<(), PluginHostError> {
+        let port = self.configure_spec.port;
+        let deadline = tokio::time::Instant::now() + STARTUP_TIMEOUT;
+        while tokio::net::TcpStream::connect(("127.0.0.1", port))
+            .await
+            .is_err()
+        {
+            if tokio::time::Instant::now() >= deadline {
+                return Err(PluginHostError::NotReady(port));
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        Ok(())
+    }
+
+    #[must_use]
+    pub const fn intro_spect(&self) -> &IntroSpect {
+        &self.intro_spect
+    }
+
+    #[must_use]
+    pub const fn configure_spec(&self) -> &ConfigureSpec {
+        &self.configure_spec
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("http://127.0.0.1:{}{path}", self.configure_spec.port)
+    }
+
+    fn next_request_id(&self) -> String {
+        let count = self.request_counter.fetch_add(1, Ordering::Relaxed);
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+        format!("{nanos:x}-{count:x}")
+    }
+
+    /// Route a request through the plugin's capture rules the way Zoraxy would
+    ///
+    /// Static capture paths are tried first, then the dynamic capture sniff.
+    /// The request's method, headers and body are forwarded to the plugin,
+    /// and the plugin's full response is returned.
+    ///
+    /// # Errors
+    /// * Returns an error if the request body can't be read, or a request to the plugin fails
+    pub async fn request(&self, req: Request<Body>) -> Result<HostResponse, PluginHostError> {
+        let request_id = self.next_request_id();
+        let (parts, body) = req.into_parts();
+        let body = body
+            .collect()
+            .await
+            .map_err(PluginHostError::ReadBody)?
+            .to_bytes();
+        let req = Request::from_parts(parts, body);
+
+        if let Some(capture_path) = self.match_static_capture(&request_uri(&req)) {
+            let resp = self.static_capture(req, &capture_path, &request_id).await?;
+            return HostResponse::from_plugin(Capture::Static(capture_path), resp).await;
+        }
+
+        if self.intro_spect.dynamic_capture_settings().is_some()
+            && self.sniff(&req, &request_id).await?
+        {
+            let resp = self.dynamic_capture(req, &request_id).await?;
+            return HostResponse::from_plugin(Capture::Dynamic, resp).await;
+        }
+
+        Ok(HostResponse {
+            capture: Capture::NotCaptured,
+            status: StatusCode::NOT_FOUND,
+            headers: HeaderMap::new(),
+            body: Bytes::new(),
+        })
+    }
+
+    /// Find the longest static capture path that `uri` falls under
+    #[must_use]
+    pub fn match_static_capture(&self, uri: &str) -> Option<String> {
+        let settings = self.intro_spect.static_capture_settings()?;
+        match_capture_path(
+            settings
+                .static_capture_paths()
+                .iter()
+                .map(crate::StaticCaptureRule::capture_path),
+            uri,
+        )
+        .map(ToString::to_string)
+    }
+
+    /// Forward a request to the static capture ingress
+    ///
+    /// # Errors
+    /// * Returns an error if the plugin has no static capture settings, or the request fails
+    pub async fn static_capture(
+        &self,
+        req: Request<Bytes>,
+        capture_path: &str,
+        request_id: &str,
+    ) -> Result<reqwest::Response, PluginHostError> {
+        let settings = self
+            .intro_spect
+            .static_capture_settings()
+            .ok_or(PluginHostError::NotDeclared("static capture settings"))?;
+        let ingress = format!(
+            "{}/",
+            settings.static_capture_ingress().trim_end_matches('/')
+        );
+        let uri = request_uri(&req);
+        debug!(target: "zoraxy::host", capture_path, uri, request_id, "Forwarding static capture");
+
+        Ok(self
+            .forward(req, &ingress)
+            .header(CAPTURE_HEADER, capture_path)
+            .header(ORIGINAL_URI_HEADER, uri)
+            .header(REQUEST_ID_HEADER, request_id)
+            .send()
+            .await?)
+    }
+
+    /// Ask the plugin's dynamic capture sniff whether it wants to capture a request
+    ///
+    /// # Errors
+    /// * Returns an error if the plugin has no dynamic capture settings, or the request fails
+    pub async fn sniff(
+        &self,
+        req: &Request<Bytes>,
+        request_id: &str,
+    ) -> Result<bool, PluginHostError> {
+        let settings = self
+            .intro_spect
+            .dynamic_capture_settings()
+            .ok_or(PluginHostError::NotDeclared("dynamic capture settings"))?;
+        let sniff_path = format!(
+            "{}/",
+            settings.dynamic_capture_sniff().trim_end_matches('/')
+        );
+        let uri = request_uri(req);
+        let host = req
+            .headers()
+            .get(header::HOST)
+            .and_then(|host| host.to_str().ok())
+            .or_else(|| {
+                req.uri()
+                    .authority()
+                    .map(axum::http::uri::Authority::as_str)
+            })
+            .unwrap_or("localhost");
+        let hostname = host.rsplit_once(':').map_or(host, |(hostname, _)| hostname);
+        let payload = serde_json::json!({
+            "method": req.method().as_str(),
+            "hostname": hostname,
+            "url": uri,
+            "header": go_header(req.headers()),
+            "remote_addr": "127.0.0.1:54321",
+            "host": host,
+            "request_uri": uri,
+            "proto": "HTTP/1.1",
+            "proto_major": 1,
+            "proto_minor": 1,
+        });
+        debug!(target: "zoraxy::host", uri, request_id, "Sniffing request");
+
+        let resp = self
+            .http
+            .post(self.url(&sniff_path))
+            .header("content-type", "application/json")
+            .header(REQUEST_ID_HEADER, request_id)
+            .body(payload.to_string())
+            .send()
+            .await?;
+        Ok(resp.status() == StatusCode::OK)
+    }
+
+    /// Forward a request to the dynamic capture ingress
+    ///
+    /// # Errors
+    /// * Returns an error if the plugin has no dynamic capture settings, or the request fails
+    pub async fn dynamic_capture(
+        &self,
+        req: Request<Bytes>,
+        request_id: &str,
+    ) -> Result<reqwest::Response, PluginHostError> {
+        let settings = self
+            .intro_spect
+            .dynamic_capture_settings()
+            .ok_or(PluginHostError::NotDeclared("dynamic capture settings"))?;
+        let path = format!(
+            "{}/{}",
+            settings.dynamic_capture_ingress().trim_end_matches('/'),
+            request_uri(&req).trim_start_matches('/')
+        );
+
+        Ok(self
+            .forward(req, &path)
+            .header(REQUEST_ID_HEADER, request_id)
+            .send()
+            .await?)
+    }
+
+    /// POST an event to the plugin's subscription path
+    ///
+    /// Returns `None` without sending anything if the plugin isn't subscribed to the event.
+    ///
+    /// # Errors
+    /// * Returns an error if the plugin has no subscriptions, or the request fails
+    pub async fn deliver_event(
+        &self,
+        event: &Event,
+    ) -> Result<Option<StatusCode>, PluginHostError> {
+        let subscriptions = self
+            .intro_spect
+            .subscriptions()
+            .ok_or(PluginHostError::NotDeclared("subscriptions"))?;
+        if !subscriptions
+            .event_subscriptions()
+            .contains_key(&event.name)
+        {
+            warn!(target: "zoraxy::host", event = %event.name, "Plugin is not subscribed to event, skipping");
+            return Ok(None);
+        }
+        let path = format!(
+            "{}/{}",
+            subscriptions.subscription_path().trim_end_matches('/'),
+            event.name
+        );
+
+        let resp = self
+            .http
+            .post(self.url(&path))
+            .header("content-type", "application/json")
+            .body(
+                serde_json::to_string(event)
+                    .map_err(|err| PluginHostError::Serialize("event", err))?,
+            )
+            .send()
+            .await?;
+        Ok(Some(resp.status()))
+    }
+
+    /// Ask the plugin to shut down via its `/term` route and wait for it to exit
+    ///
+    /// The plugin is killed if it has no UI path, or doesn't exit in time.
+    ///
+    /// # Errors
+    /// * Returns an error if waiting for the plugin fails
+    pub async fn terminate(mut self) -> Result<ExitStatus, PluginHostError> {
+        if let Some(ui_path) = self.intro_spect.ui_path() {
+            let term_path = format!("{}/term", ui_path.trim_end_matches('/'));
+            if let Err(err) = self.http.get(self.url(&term_path)).send().await {
+                warn!(target: "zoraxy::host", %err, "Failed to call plugin termination route");
+            }
+        }
+
+        if let Ok(status) = tokio::time::timeout(SHUTDOWN_TIMEOUT, self.child.wait()).await {
+            return status.map_err(PluginHostError::Wait);
+        }
+        warn!(target: "zoraxy::host", "Plugin did not exit in time, killing it");
+        self.child.kill().await.map_err(PluginHostError::Wait)?;
+        self.child.wait().await.map_err(PluginHostError::Wait)
+    }
+
+    /// Build a request to `path` on the plugin with the method, headers and body of `req`
+    fn forward(&self, req: Request<Bytes>, path: &str) -> reqwest::RequestBuilder {
+        let (parts, body) = req.into_parts();
+        let mut headers = parts.headers;
+        for name in HOP_BY_HOP_HEADERS
+            .into_iter()
+            .chain([header::HOST, header::CONTENT_LENGTH])
+        {
+            headers.remove(name);
+        }
+        self.http
+            .request(parts.method, self.url(path))
+            .headers(headers)
+            .body(body)
+    }
+}
+
+/// The path and query of a request, the way Zoraxy sees the request URI
+fn request_uri<B>(req: &Request<B>) -> String {
+    req.uri()
+        .path_and_query()
+        .map_or_else(|| "/".to_string(), ToString::to_string)
+}
+
+/// Group headers by name the way Go's `http.Header` does, with canonical names like `Content-Type`
+fn go_header(headers: &HeaderMap) -> HashMap<String, Vec<String>> {
+    let mut header: HashMap<String, Vec<String>> = HashMap::new();
+    for (name, value) in headers {
+        if name == header::HOST {
+            continue;
+        }
+        let name = name
+            .as_str()
+            .split('-')
+            .map(|part| {
+                let mut chars = part.chars();
+                chars.next().map_or_else(String::new, |first| {
+                    first.to_ascii_uppercase().to_string() + chars.as_str()
+                })
+            })
+            .collect::<Vec<_>>()
+            .join("-");
+        header
+            .entry(name)
+            .or_default()
+            .push(String::from_utf8_lossy(value.as_bytes()).into_owned());
+    }
+    header
+}
+
+/// Find the longest capture path that `uri` is equal to, or a subpath of
+fn match_capture_path<'a>(
+    capture_paths: impl IntoIterator<Item = &'a str>,
+    uri: &str,
+) -> Option<&'a str> {
+    let path = uri.split(['?', '#']).next().unwrap_or_default();
+    capture_paths
+        .into_iter()
+        .filter(|capture_path| {
+            let capture_path = capture_path.trim_end_matches('/');
+            capture_path.is_empty()
+                || path == capture_path
+                || path
+                    .strip_prefix(capture_path)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        })
+        .max_by_key(|capture_path| capture_path.trim_end_matches('/').len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_longest_capture_path() {
+        let paths = ["/test_a", "/test_a/deep", "/test_b/"];
+
+        assert_eq!(match_capture_path(paths, "/test_a"), Some("/test_a"));
+        assert_eq!(match_capture_path(paths, "/test_a/x?y=1"), Some("/test_a"));
+        assert_eq!(
+            match_capture_path(paths, "/test_a/deep/x"),
+            Some("/test_a/deep")
+        );
+        assert_eq!(match_capture_path(paths, "/test_b/x"), Some("/test_b/"));
+        assert_eq!(match_capture_path(paths, "/test_ab"), None);
+    }
+
+    #[test]
+    fn groups_headers_like_go() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, "example.com".parse().unwrap());
+        headers.insert(header::CONTENT_TYPE, "text/plain".parse().unwrap());
+        headers.append("x-forwarded-for", "10.0.0.1".parse().unwrap());
+        headers.append("x-forwarded-for", "10.0.0.2".parse().unwrap());
+
+        let header = go_header(&headers);
+        assert_eq!(header.len(), 2);
+        assert_eq!(header["Content-Type"], ["text/plain"]);
+        assert_eq!(header["X-Forwarded-For"], ["10.0.0.1", "10.0.0.2"]);
+    }
+}
@@ -1,9 +1,21 @@
+#[cfg(feature = "build")]
+pub mod build;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod context;
 pub mod dynamic_router;
 pub mod embed_webserver;
+#[cfg(feature = "host")]
+pub mod host;
+pub mod metadata;
+pub mod metrics;
+pub mod plugin;
 pub mod prelude;
 pub mod spec;
 pub mod static_router;
 mod termination;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod types;
 
 pub use prelude::*;
@@ -29,6 +41,29 @@ pub fn init_tracing_subscriber(debug: bool) {
         .init();
 }
 
+/// Adds the `{ui_path}/term` route Zoraxy uses to ask the plugin to shut down
+pub(crate) fn with_termination_route<S>(
+    app: axum::Router<S>,
+    ui_path: &str,
+    terminator: termination::Terminator,
+) -> axum::Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    // listen for termination requests from zoraxy via HTTP GET /term/
+    let termination_handler = async move || {
+        if let Err(e) = terminator.terminate(termination::Interrupted::UserInt) {
+            tracing::error!("Failed to send termination signal: {e:?}");
+            axum::http::StatusCode::INTERNAL_SERVER_ERROR
+        } else {
+            axum::http::StatusCode::OK
+        }
+    };
+
+    let term_path = format!("{}/term", ui_path.trim_end_matches('/'));
+    app.route(&term_path, axum::routing::get(termination_handler))
+}
+
 /// Starts the axum web server for the plugin, handling termination signals from Zoraxy if the `ui_path` is provided.
 /// # Arguments
 /// * `app` - The axum Router instance to serve.
@@ -52,20 +87,8 @@ where
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
 
-    // listen for termination requests from zoraxy via HTTP POST /term/
-    let termination_handler = async move |_: axum::extract::State<S>| {
-        if let Err(e) = terminator.terminate(termination::Interrupted::UserInt) {
-            tracing::error!("Failed to send termination signal: {e:?}");
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR
-        } else {
-            axum::http::StatusCode::OK
-        }
-    };
-
     let app = if let Some(ui_path) = ui_path {
-        let term_path = format!("{}/term", ui_path.as_ref().trim_end_matches('/'));
-
-        app.route(&term_path, axum::routing::get(termination_handler.clone()))
+        with_termination_route(app, ui_path.as_ref(), terminator)
     } else {
         app
     };
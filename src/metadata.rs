@@ -0,0 +1,245 @@
+//! Generate `PluginMetadata` from the Cargo package metadata of the plugin crate, see [`plugin_metadata!`](crate::plugin_metadata).
+
+use crate::types::{PluginMetadata, PluginType};
+
+/// Create a `PluginMetadata` from the Cargo package metadata of the calling crate
+///
+/// * `name`, `description` and `version` are taken from the package
+/// * `author` is taken from the package authors, without their email addresses
+/// * `url` is taken from the package homepage, falling back to the repository
+/// * `id` and `contact` are taken from the `[package.metadata.zoraxy]` table, if the plugin has one,
+///   `id` falls back to the package name and `contact` to the email address of the first author
+///
+/// ```toml
+/// [package.metadata.zoraxy]
+/// id = "com.example.myplugin"
+/// contact = "admin@example.com"
+/// ```
+///
+/// Cargo doesn't pass package metadata to the compiler, the table is read by calling
+/// [`build::emit_plugin_metadata`](crate::build::emit_plugin_metadata) from the plugin's build script.
+/// Plugins without a build script can pass the id, and optionally the contact, to the macro instead,
+/// the `[package.metadata.zoraxy]` table is then ignored.
+///
+/// Compilation fails if the plugin id is empty,
+/// and if a component of the package version doesn't fit in a `u8`.
+///
+/// ```ignore
+/// use zoraxy_rs::prelude::*;
+///
+/// // id and contact from the `[package.metadata.zoraxy]` table, or the fallbacks above
+/// let intro_spect = IntroSpect::new(zoraxy_rs::plugin_metadata!(PluginType::Router));
+/// // id and contact set explicitly
+/// let metadata = zoraxy_rs::plugin_metadata!(
+///     PluginType::Router,
+///     id = "com.example.myplugin",
+///     contact = "admin@example.com",
+/// );
+/// ```
+#[macro_export]
+macro_rules! plugin_metadata {
+    ($plugin_type:expr $(,)?) => {
+        $crate::plugin_metadata!(
+            @zoraxy_table $plugin_type,
+            match option_env!("ZORAXY_PLUGIN_ID") {
+                Some(id) => id,
+                None => env!("CARGO_PKG_NAME"),
+            },
+            option_env!("ZORAXY_PLUGIN_CONTACT")
+        )
+    };
+    ($plugin_type:expr, id = $id:expr $(, contact = $contact:expr)? $(,)?) => {
+        $crate::plugin_metadata!(
+            @zoraxy_table $plugin_type,
+            $id,
+            $crate::plugin_metadata!(@contact $($contact)?)
+        )
+    };
+    (@contact) => {
+        None
+    };
+    (@contact $contact:expr) => {
+        Some($contact)
+    };
+    (@zoraxy_table $plugin_type:expr, $id:expr, $contact:expr) => {
+        $crate::metadata::from_cargo_package(
+            $plugin_type,
+            $crate::metadata::CargoPackage {
+                name: env!("CARGO_PKG_NAME"),
+                version: const {
+                    (
+                        $crate::metadata::parse_version_component(env!("CARGO_PKG_VERSION_MAJOR")),
+                        $crate::metadata::parse_version_component(env!("CARGO_PKG_VERSION_MINOR")),
+                        $crate::metadata::parse_version_component(env!("CARGO_PKG_VERSION_PATCH")),
+                    )
+                },
+                description: env!("CARGO_PKG_DESCRIPTION"),
+                authors: env!("CARGO_PKG_AUTHORS"),
+                homepage: env!("CARGO_PKG_HOMEPAGE"),
+                repository: env!("CARGO_PKG_REPOSITORY"),
+                id: const { $crate::metadata::require_id($id) },
+                contact: $contact,
+            },
+        )
+    };
+}
+
+/// Cargo package metadata collected by [`plugin_metadata!`]
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy)]
+pub struct CargoPackage {
+    pub name: &'static str,
+    pub version: (u8, u8, u8),
+    pub description: &'static str,
+    /// Colon separated list of authors, as in `CARGO_PKG_AUTHORS`
+    pub authors: &'static str,
+    pub homepage: &'static str,
+    pub repository: &'static str,
+    /// `id` from the `[package.metadata.zoraxy]` table or the macro arguments
+    pub id: &'static str,
+    /// `contact` from the `[package.metadata.zoraxy]` table or the macro arguments
+    pub contact: Option<&'static str>,
+}
+
+/// Check that the plugin id isn't empty, panics (failing compilation in const context) if it is
+#[doc(hidden)]
+#[must_use]
+pub const fn require_id(id: &'static str) -> &'static str {
+    assert!(
+        !id.trim_ascii().is_empty(),
+        "the plugin id is empty, set `id` in the [package.metadata.zoraxy] table of Cargo.toml or pass it to plugin_metadata!"
+    );
+    id
+}
+
+/// Parse a version component, panics (failing compilation in const context) if it doesn't fit in a `u8`
+#[doc(hidden)]
+#[must_use]
+pub const fn parse_version_component(component: &str) -> u8 {
+    let bytes = component.as_bytes();
+    assert!(!bytes.is_empty(), "version component is empty");
+
+    let mut value: u16 = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        assert!(
+            bytes[i].is_ascii_digit(),
+            "version component is not a number"
+        );
+        value = value * 10 + (bytes[i] - b'0') as u16;
+        assert!(
+            value <= u8::MAX as u16,
+            "version component does not fit in a u8, Zoraxy plugin versions are limited to 255.255.255"
+        );
+        i += 1;
+    }
+    #[allow(clippy::cast_possible_truncation)]
+    let value = value as u8;
+    value
+}
+
+/// Build a `PluginMetadata` from the collected Cargo package metadata
+#[doc(hidden)]
+#[must_use]
+pub fn from_cargo_package(plugin_type: PluginType, package: CargoPackage) -> PluginMetadata {
+    let authors: Vec<&str> = package
+        .authors
+        .split(':')
+        .map(str::trim)
+        .filter(|author| !author.is_empty())
+        .collect();
+    let author_names: Vec<&str> = authors
+        .iter()
+        .map(|author| author.split('<').next().unwrap_or_default().trim())
+        .collect();
+    let first_author_email = authors.first().and_then(|author| {
+        let (_, email) = author.split_once('<')?;
+        email.strip_suffix('>')
+    });
+
+    let contact = package.contact.or(first_author_email).unwrap_or_default();
+    let url = if package.homepage.is_empty() {
+        package.repository
+    } else {
+        package.homepage
+    };
+
+    PluginMetadata::new(plugin_type)
+        .with_id(package.id)
+        .with_name(package.name)
+        .with_author(author_names.join(", "))
+        .with_contact(contact)
+        .with_description(package.description)
+        .with_url(url)
+        .with_version(package.version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parses_version_components() {
+        assert_eq!(parse_version_component("0"), 0);
+        assert_eq!(parse_version_component("42"), 42);
+        assert_eq!(parse_version_component("255"), 255);
+        assert!(std::panic::catch_unwind(|| parse_version_component("256")).is_err());
+    }
+
+    #[test]
+    fn builds_metadata_from_package() {
+        let metadata = from_cargo_package(
+            PluginType::Router,
+            CargoPackage {
+                name: "myplugin",
+                version: (1, 2, 3),
+                description: "My plugin",
+                authors: "Jane Doe <jane@example.com>:John Doe",
+                homepage: "",
+                repository: "https://example.com/repo",
+                id: "com.example.myplugin",
+                contact: None,
+            },
+        );
+
+        assert_eq!(metadata.id(), "com.example.myplugin");
+        assert_eq!(metadata.name(), "myplugin");
+        assert_eq!(metadata.author(), "Jane Doe, John Doe");
+        assert_eq!(metadata.contact(), "jane@example.com");
+        assert_eq!(metadata.url(), "https://example.com/repo");
+        assert_eq!(metadata.version(), (1, 2, 3));
+    }
+
+    #[test]
+    fn macro_reads_this_crate() {
+        let metadata = crate::plugin_metadata!(
+            @zoraxy_table PluginType::Utilities,
+            "com.example.test",
+            Some("admin@example.com")
+        );
+
+        assert_eq!(metadata.name(), "zoraxy-rs");
+        assert_eq!(metadata.author(), "Anthony Rubick");
+        assert_eq!(metadata.id(), "com.example.test");
+        assert_eq!(metadata.contact(), "admin@example.com");
+        assert_eq!(metadata.url(), "https://zoraxy.aroz.org/");
+        assert_eq!(metadata.plugin_type(), PluginType::Utilities);
+    }
+
+    #[test]
+    fn macro_falls_back_without_zoraxy_table() {
+        let metadata = crate::plugin_metadata!(PluginType::Router);
+        assert_eq!(metadata.id(), "zoraxy-rs");
+
+        let metadata = crate::plugin_metadata!(PluginType::Router, id = "com.example.test");
+        assert_eq!(metadata.id(), "com.example.test");
+
+        let metadata = crate::plugin_metadata!(
+            PluginType::Router,
+            id = "com.example.test",
+            contact = "admin@example.com",
+        );
+        assert_eq!(metadata.contact(), "admin@example.com");
+    }
+}
@@ -0,0 +1,364 @@
+//! Built-in metrics for capture dispatch, see [`CaptureMetrics`].
+
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt::Write;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{Request, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::MethodRouter;
+use tower::{Layer, Service};
+
+/// Upper bounds of the capture duration histogram buckets, in seconds
+const DURATION_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// `pattern` label of requests served by the `StaticPathRouter`'s default handler
+pub(crate) const DEFAULT_HANDLER_LABEL: &str = "default";
+
+/// Counters and histograms of captured traffic, rendered in the Prometheus text format
+///
+/// * `zoraxy_static_dispatch_total{capture_path, pattern}`: static capture requests by the capture path
+///   Zoraxy sent and the pattern that handled it, `default` for the default handler
+/// * `zoraxy_sniff_total{decision}`: dynamic capture sniffs by decision, `accept`, `skip` or `error`
+/// * `zoraxy_capture_duration_seconds{handler}`: time until the capture handler responded,
+///   by the handler's pattern (`default` for the default handler) for static capture and by ingress for dynamic capture
+///
+/// Attach it with `StaticPathRouter::with_metrics`, `DynamicCaptureService::with_metrics`,
+/// `DynamicRouter::with_metrics` and [`CaptureMetrics::sniff_layer`],
+/// then serve it with [`CaptureMetrics::prometheus_route`]:
+///
+/// ```
+/// use std::sync::Arc;
+/// use axum::Router;
+/// use zoraxy_rs::prelude::*;
+///
+/// let metrics = Arc::new(CaptureMetrics::new());
+/// let router = StaticPathRouter::default().with_metrics(metrics.clone());
+/// let ui_router: Router = Router::new().route("/metrics", metrics.prometheus_route());
+/// ```
+#[derive(Debug, Default)]
+pub struct CaptureMetrics {
+    static_dispatch: Family<Counter>,
+    sniffs: Family<Counter>,
+    capture_duration: Family<Histogram>,
+}
+
+impl CaptureMetrics {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn record_static_dispatch(
+        &self,
+        capture_path: &str,
+        pattern: &str,
+        duration: Duration,
+    ) {
+        self.static_dispatch
+            .get(&labels(&[
+                ("capture_path", capture_path),
+                ("pattern", pattern),
+            ]))
+            .increment();
+        self.record_capture(pattern, duration);
+    }
+
+    pub(crate) fn record_capture(&self, handler: &str, duration: Duration) {
+        self.capture_duration
+            .get(&labels(&[("handler", handler)]))
+            .observe(duration);
+    }
+
+    pub(crate) fn record_sniff(&self, status: StatusCode) {
+        let decision = match status {
+            StatusCode::OK => "accept",
+            StatusCode::NOT_IMPLEMENTED => "skip",
+            _ => "error",
+        };
+        self.sniffs
+            .get(&labels(&[("decision", decision)]))
+            .increment();
+    }
+
+    /// A `tower::Layer` for the sniff route counting the sniff decisions
+    #[must_use]
+    pub fn sniff_layer(self: &Arc<Self>) -> SniffMetricsLayer {
+        SniffMetricsLayer {
+            metrics: self.clone(),
+        }
+    }
+
+    /// Render the metrics in the Prometheus text exposition format
+    #[must_use]
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        self.static_dispatch.render_counters(
+            &mut out,
+            "zoraxy_static_dispatch_total",
+            "Static capture requests by capture path and handling pattern",
+        );
+        self.sniffs.render_counters(
+            &mut out,
+            "zoraxy_sniff_total",
+            "Dynamic capture sniffs by decision",
+        );
+        self.capture_duration.render_histograms(
+            &mut out,
+            "zoraxy_capture_duration_seconds",
+            "Time until the capture handler responded, by handler",
+        );
+        out
+    }
+
+    /// A GET route serving the rendered metrics, to be mounted e.g. under the plugin's UI path
+    pub fn prometheus_route<S>(self: &Arc<Self>) -> MethodRouter<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        let metrics = self.clone();
+        axum::routing::get(async move || {
+            (
+                [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+                metrics.render(),
+            )
+                .into_response()
+        })
+    }
+}
+
+/// Layer counting sniff decisions, see [`CaptureMetrics::sniff_layer`]
+#[derive(Debug, Clone)]
+pub struct SniffMetricsLayer {
+    metrics: Arc<CaptureMetrics>,
+}
+
+impl<S> Layer<S> for SniffMetricsLayer {
+    type Service = SniffMetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SniffMetricsService {
+            inner,
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SniffMetricsService<S> {
+    inner: S,
+    metrics: Arc<CaptureMetrics>,
+}
+
+impl<S> Service<Request<Body>> for SniffMetricsService<S>
+where
+    S: Service<Request<Body>, Response = Response, Error = Infallible>,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let metrics = self.metrics.clone();
+        let future = self.inner.call(req);
+        Box::pin(async move {
+            let resp = future.await?;
+            metrics.record_sniff(resp.status());
+            Ok(resp)
+        })
+    }
+}
+
+/// Metrics of one name, by their rendered label set
+#[derive(Debug)]
+struct Family<M> {
+    metrics: RwLock<BTreeMap<String, Arc<M>>>,
+}
+
+impl<M> Default for Family<M> {
+    fn default() -> Self {
+        Self {
+            metrics: RwLock::new(BTreeMap::new()),
+        }
+    }
+}
+
+impl<M: Default> Family<M> {
+    fn get(&self, labels: &str) -> Arc<M> {
+        if let Some(metric) = self
+            .metrics
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .get(labels)
+        {
+            return metric.clone();
+        }
+        self.metrics
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .entry(labels.to_string())
+            .or_default()
+            .clone()
+    }
+
+    fn snapshot(&self) -> Vec<(String, Arc<M>)> {
+        self.metrics
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .iter()
+            .map(|(labels, metric)| (labels.clone(), metric.clone()))
+            .collect()
+    }
+}
+
+impl Family<Counter> {
+    fn render_counters(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} counter");
+        for (labels, counter) in self.snapshot() {
+            let _ = writeln!(
+                out,
+                "{name}{{{labels}}} {}",
+                counter.0.load(Ordering::Relaxed)
+            );
+        }
+    }
+}
+
+impl Family<Histogram> {
+    fn render_histograms(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} histogram");
+        for (labels, histogram) in self.snapshot() {
+            let mut cumulative = 0;
+            for (bound, bucket) in DURATION_BUCKETS.iter().zip(&histogram.buckets) {
+                cumulative += bucket.load(Ordering::Relaxed);
+                let _ = writeln!(out, "{name}_bucket{{{labels},le=\"{bound}\"}} {cumulative}");
+            }
+            let count = histogram.count.load(Ordering::Relaxed);
+            #[allow(clippy::cast_precision_loss)]
+            let sum = histogram.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+            let _ = writeln!(out, "{name}_bucket{{{labels},le=\"+Inf\"}} {count}");
+            let _ = writeln!(out, "{name}_sum{{{labels}}} {sum}");
+            let _ = writeln!(out, "{name}_count{{{labels}}} {count}");
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counter(AtomicU64);
+
+impl Counter {
+    fn increment(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Default)]
+struct Histogram {
+    /// Non-cumulative bucket counts, cumulated when rendering
+    buckets: [AtomicU64; DURATION_BUCKETS.len()],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        if let Some(index) = DURATION_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.buckets[index].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        let micros = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+        self.sum_micros.fetch_add(micros, Ordering::Relaxed);
+    }
+}
+
+/// Render a label set, escaping the values as the Prometheus text format requires
+fn labels(pairs: &[(&str, &str)]) -> String {
+    let rendered: Vec<String> = pairs
+        .iter()
+        .map(|(name, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{name}=\"{value}\"")
+        })
+        .collect();
+    rendered.join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamic_router::{DynamicCaptureService, SniffDecision};
+    use crate::static_router::StaticPathRouter;
+    use axum::handler::HandlerWithoutStateExt;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn records_and_renders_capture_metrics() {
+        let metrics = Arc::new(CaptureMetrics::new());
+
+        let router = StaticPathRouter::default().with_metrics(metrics.clone());
+        router.register_path_service("/test_a/*", (async || "a").into_service());
+        for capture_path in ["/test_a", "/test_a", "/unknown"] {
+            let req = Request::builder()
+                .uri("/s_capture/")
+                .header("x-zoraxy-capture", capture_path)
+                .body(Body::empty())
+                .unwrap();
+            router.dispatch_capture(req).await.unwrap();
+        }
+
+        let capture = DynamicCaptureService::new("/d_capture", (async || "d").into_service())
+            .with_metrics(metrics.clone());
+        let req = Request::builder()
+            .uri("/d_capture/x")
+            .body(Body::empty())
+            .unwrap();
+        capture.oneshot(req).await.unwrap();
+
+        let sniff = metrics
+            .sniff_layer()
+            .layer((async || SniffDecision::Skip).into_service());
+        sniff.oneshot(Request::new(Body::empty())).await.unwrap();
+
+        let rendered = metrics.render();
+        for line in [
+            r#"zoraxy_static_dispatch_total{capture_path="/test_a",pattern="/test_a/*"} 2"#,
+            r#"zoraxy_static_dispatch_total{capture_path="/unknown",pattern="default"} 1"#,
+            r#"zoraxy_sniff_total{decision="skip"} 1"#,
+            r#"zoraxy_capture_duration_seconds_count{handler="/test_a/*"} 2"#,
+            r#"zoraxy_capture_duration_seconds_bucket{handler="/d_capture/",le="+Inf"} 1"#,
+        ] {
+            assert!(
+                rendered.lines().any(|l| l == line),
+                "{line} missing from\n{rendered}"
+            );
+        }
+    }
+
+    #[test]
+    fn escapes_label_values() {
+        assert_eq!(
+            labels(&[("capture_path", "/a\"b\\c\n")]),
+            r#"capture_path="/a\"b\\c\n""#
+        );
+    }
+}
@@ -0,0 +1,247 @@
+use std::future::Future;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+
+use anyhow::{Result, bail};
+use axum::Router;
+use axum::http::Uri;
+use axum::response::Redirect;
+use axum::routing::any;
+
+use crate::spec::Handshake;
+use crate::static_router::StaticPathRouter;
+use crate::types::{ConfigureSpec, IntroSpect, ZoraxyVersion};
+use crate::{init_tracing_subscriber, start_plugin};
+
+/// A Zoraxy plugin, run with [`run`]
+///
+/// `run` performs the whole plugin lifecycle in the right order:
+/// 1. serve the `IntroSpect` returned by `introspect` if started with `-introspect`
+/// 2. receive the `ConfigureSpec`, initialize logging, check `MIN_ZORAXY_VERSION` and `configure` the plugin
+/// 3. build the routers, mounting the UI router and the termination route under the `IntroSpect`'s UI path,
+///    and check the `static_path_router` against the advertised capture paths
+/// 4. call `on_start`, serve the plugin until Zoraxy terminates it, then call `on_shutdown`
+pub trait ZoraxyPlugin: Sized + Send + Sync {
+    /// The oldest Zoraxy version the plugin supports, checked before `configure`
+    ///
+    /// Development builds of Zoraxy only get a warning, see [`RuntimeConstants::require_version`](crate::RuntimeConstants::require_version).
+    const MIN_ZORAXY_VERSION: Option<ZoraxyVersion> = None;
+
+    /// The `IntroSpect` describing the plugin to Zoraxy
+    fn introspect() -> IntroSpect;
+
+    /// Create the plugin from the `ConfigureSpec` received from Zoraxy,
+    /// this is where state shared between the routers should be set up
+    ///
+    /// # Errors
+    /// * Returning an error aborts the plugin before it starts serving
+    fn configure(spec: &ConfigureSpec) -> Result<Self>;
+
+    /// Build the router serving the plugin's capture and subscription paths
+    ///
+    /// # Errors
+    /// * Returning an error aborts the plugin before it starts serving
+    fn build_router(&self, spec: &ConfigureSpec) -> Result<Router>;
+
+    /// Build the router serving the plugin UI, it is nested under the `IntroSpect`'s UI path
+    ///
+    /// # Errors
+    /// * Returning an error aborts the plugin before it starts serving
+    fn build_ui_router(&self, _spec: &ConfigureSpec) -> Result<Option<Router>> {
+        Ok(None)
+    }
+
+    /// The `StaticPathRouter` serving the static capture ingress, if the plugin has one
+    ///
+    /// `run` checks it against the `IntroSpect`'s static capture settings once the routers are built,
+    /// and logs a warning for every mismatch, see [`StaticPathRouter::check_capture_settings`].
+    fn static_path_router(&self) -> Option<Arc<StaticPathRouter>> {
+        None
+    }
+
+    /// Whether to log at debug level, defaults to true for development builds of Zoraxy
+    #[must_use]
+    fn debug_logging(spec: &ConfigureSpec) -> bool {
+        spec.runtime_constants.development_build
+    }
+
+    /// Called once the plugin is configured, just before it starts serving
+    ///
+    /// # Errors
+    /// * Returning an error aborts the plugin before it starts serving
+    fn on_start(&self, _spec: &ConfigureSpec) -> impl Future<Output = Result<()>> + Send {
+        async { Ok(()) }
+    }
+
+    /// Called after the plugin has stopped serving
+    fn on_shutdown(&self) -> impl Future<Output = ()> + Send {
+        async {}
+    }
+}
+
+/// Run the plugin `P`, see [`ZoraxyPlugin`] for the lifecycle
+///
+/// # Errors
+/// * Returns an error if the handshake with Zoraxy fails
+/// * Returns an error if Zoraxy is older than `P::MIN_ZORAXY_VERSION`
+/// * Returns an error if building the routers or `on_start` fails
+/// * Returns an error if the server fails to start or encounters an error during execution
+pub async fn run<P: ZoraxyPlugin>() -> Result<()> {
+    let intro_spect = P::introspect();
+
+    let spec = match Handshake::from_args(std::env::args(), &intro_spect)? {
+        Handshake::Introspect(intro_spect_json) => {
+            println!("{intro_spect_json}");
+            return Ok(());
+        }
+        Handshake::Configure(spec) => spec,
+    };
+
+    init_tracing_subscriber(P::debug_logging(&spec));
+
+    if let Some(required) = P::MIN_ZORAXY_VERSION
+        && let Err(err) = spec.runtime_constants.require_version(required)
+    {
+        tracing::error!("{}: {err}", intro_spect.metadata().name());
+        return Err(err.into());
+    }
+
+    let plugin = P::configure(&spec)?;
+    let app = build_app(&plugin, &intro_spect, &spec)?;
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, spec.port));
+
+    plugin.on_start(&spec).await?;
+    tracing::info!(
+        "{} listening on http://{addr}",
+        intro_spect.metadata().name()
+    );
+    let result = start_plugin(app, (), addr, intro_spect.ui_path()).await;
+    plugin.on_shutdown().await;
+    result
+}
+
+fn build_app<P: ZoraxyPlugin>(
+    plugin: &P,
+    intro_spect: &IntroSpect,
+    spec: &ConfigureSpec,
+) -> Result<Router> {
+    let app = plugin.build_router(spec)?;
+    if let Some(router) = plugin.static_path_router()
+        && let Some(settings) = intro_spect.static_capture_settings()
+    {
+        router.check_capture_settings(settings);
+    }
+
+    match (plugin.build_ui_router(spec)?, intro_spect.ui_path()) {
+        (Some(ui_router), Some(ui_path)) => {
+            let ui_path = ui_path.trim_end_matches('/').to_string();
+            if ui_path.is_empty() {
+                return Ok(app.fallback_service(ui_router));
+            }
+            // the UI is served under `<ui_path>/`, redirect the bare path there so relative links resolve
+            let ui_index = format!("{ui_path}/");
+            let redirect = any(move |uri: Uri| async move {
+                let query = uri.query().map(|query| format!("?{query}"));
+                Redirect::permanent(&format!("{ui_index}{}", query.unwrap_or_default()))
+            });
+            Ok(app
+                .route(&ui_path, redirect)
+                .nest_service(&format!("{ui_path}/"), ui_router))
+        }
+        (Some(_), None) => bail!("plugin has a UI router, but its IntroSpect has no UI path"),
+        (None, _) => Ok(app),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PluginMetadata, PluginType};
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::routing::get;
+    use tower::ServiceExt;
+
+    struct TestPlugin;
+
+    impl ZoraxyPlugin for TestPlugin {
+        fn introspect() -> IntroSpect {
+            IntroSpect::new(PluginMetadata::new(PluginType::Utilities).with_id("com.example.test"))
+                .with_ui_path("/ui")
+        }
+
+        fn configure(_spec: &ConfigureSpec) -> Result<Self> {
+            Ok(Self)
+        }
+
+        fn build_router(&self, _spec: &ConfigureSpec) -> Result<Router> {
+            Ok(Router::new().route("/api", get(async || "api")))
+        }
+
+        fn build_ui_router(&self, _spec: &ConfigureSpec) -> Result<Option<Router>> {
+            Ok(Some(Router::new().route("/", get(async || "ui index"))))
+        }
+    }
+
+    fn spec() -> ConfigureSpec {
+        serde_json::from_str(
+            r#"{"port":8080,"runtime_const":{"zoraxy_version":"3.2.9","zoraxy_uuid":"x","development_build":false}}"#,
+        )
+        .unwrap()
+    }
+
+    async fn get_text(app: &Router, uri: &str) -> (StatusCode, String) {
+        let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        let status = resp.status();
+        let body = axum::body::to_bytes(resp.into_body(), 1024).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn nests_ui_router_under_ui_path() {
+        let app = build_app(&TestPlugin, &TestPlugin::introspect(), &spec()).unwrap();
+
+        assert_eq!(
+            get_text(&app, "/ui/").await,
+            (StatusCode::OK, "ui index".to_string())
+        );
+        assert_eq!(
+            get_text(&app, "/api").await,
+            (StatusCode::OK, "api".to_string())
+        );
+
+        let req = Request::builder()
+            .uri("/ui?tab=1")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(resp.headers()["location"], "/ui/?tab=1");
+    }
+
+    #[tokio::test]
+    async fn serves_root_ui_path_as_fallback() {
+        let intro_spect =
+            IntroSpect::new(PluginMetadata::new(PluginType::Utilities).with_id("com.example.test"))
+                .with_ui_path("/");
+        let app = build_app(&TestPlugin, &intro_spect, &spec()).unwrap();
+
+        assert_eq!(
+            get_text(&app, "/").await,
+            (StatusCode::OK, "ui index".to_string())
+        );
+        assert_eq!(
+            get_text(&app, "/api").await,
+            (StatusCode::OK, "api".to_string())
+        );
+    }
+
+    #[test]
+    fn ui_router_requires_ui_path() {
+        let intro_spect =
+            IntroSpect::new(PluginMetadata::new(PluginType::Utilities).with_id("com.example.test"));
+
+        assert!(build_app(&TestPlugin, &intro_spect, &spec()).is_err());
+    }
+}
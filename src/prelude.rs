@@ -1,6 +1,11 @@
+#[cfg(feature = "client")]
+pub use crate::client::*;
+pub use crate::context::*;
 pub use crate::dynamic_router::*;
 pub use crate::embed_webserver::*;
 pub use crate::init_tracing_subscriber;
+pub use crate::metrics::*;
+pub use crate::plugin::*;
 pub use crate::spec::*;
 pub use crate::start_plugin;
 pub use crate::static_router::*;
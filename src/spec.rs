@@ -1,4 +1,171 @@
-use anyhow::{Result, bail};
+use anyhow::Result;
+
+use crate::types::{ConfigureSpec, IntroSpect, IntroSpectValidationError, RuntimeConstants};
+
+/// Errors that can occur during the handshake with Zoraxy
+#[derive(Debug, thiserror::Error)]
+pub enum HandshakeError {
+    /// The expected flag was not passed to the plugin
+    #[error("no {0} flag found")]
+    MissingFlag(&'static str),
+    /// The flag was passed, but without a value
+    #[error("no value provided after {0}")]
+    MissingValue(&'static str),
+    /// The value passed to the flag is not valid JSON for the expected payload
+    #[error("malformed {flag} JSON at `{path}`: {source}")]
+    MalformedJson {
+        flag: &'static str,
+        /// Path to the offending value within the payload, e.g. `runtime_const.zoraxy_version`
+        path: String,
+        #[source]
+        source: serde_json::Error,
+    },
+    /// The configure spec file referenced with `-configure @path` could not be read
+    #[error("failed to read configure spec file {path}: {source}")]
+    SpecFile {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+    /// An environment variable used in development mode has an invalid value
+    #[error("invalid value {value:?} for environment variable {name}")]
+    InvalidEnvVar { name: &'static str, value: String },
+    /// No free port could be found for the plugin to listen on in development mode
+    #[error("failed to find a free port: {0}")]
+    NoFreePort(#[source] std::io::Error),
+    /// The plugin's `IntroSpect` failed validation
+    #[error(transparent)]
+    InvalidIntroSpect(#[from] IntroSpectValidationError),
+    /// The plugin's `IntroSpect` could not be serialized to JSON
+    #[error("failed to serialize IntroSpect: {0}")]
+    SerializeIntroSpect(#[source] serde_json::Error),
+}
+
+/// Environment variable that enables development mode, equivalent to passing `-dev`
+pub const DEV_MODE_ENV: &str = "ZORAXY_DEV";
+/// Port the plugin listens on in development mode, defaults to a free port
+pub const DEV_PORT_ENV: &str = "ZORAXY_DEV_PORT";
+/// API key handed to the plugin in development mode, defaults to none
+pub const DEV_API_KEY_ENV: &str = "ZORAXY_DEV_API_KEY";
+/// Port of the Zoraxy instance used in development mode, defaults to none
+pub const DEV_ZORAXY_PORT_ENV: &str = "ZORAXY_DEV_ZORAXY_PORT";
+/// Zoraxy version reported in development mode, defaults to `DEV_ZORAXY_VERSION`
+pub const DEV_ZORAXY_VERSION_ENV: &str = "ZORAXY_DEV_ZORAXY_VERSION";
+/// Zoraxy version reported in development mode if `ZORAXY_DEV_ZORAXY_VERSION` is not set
+pub const DEV_ZORAXY_VERSION: &str = "3.2.9";
+
+/// The outcome of the handshake with Zoraxy
+#[derive(Debug, Clone)]
+pub enum Handshake {
+    /// Zoraxy started the plugin with the -introspect flag,
+    /// the plugin should print the contained JSON to stdout and exit
+    Introspect(String),
+    /// Zoraxy started the plugin with the -configure flag,
+    /// the plugin should configure itself with the contained spec and start serving
+    Configure(ConfigureSpec),
+}
+
+impl Handshake {
+    /// Determine which stage of the handshake the plugin was started for
+    ///
+    /// Unlike `serve_and_recv_spec`, this never exits the process.
+    ///
+    /// If the plugin is started with neither -introspect nor -configure, but with the `-dev` flag
+    /// or the `ZORAXY_DEV` environment variable set, a configure spec is synthesized for local
+    /// development, see `dev_configuration_spec`.
+    ///
+    /// # Arguments
+    /// * `args` - The command line arguments, including the program name
+    /// * `intro_spect` - The `IntroSpect` of the plugin
+    ///
+    /// # Errors
+    /// * `HandshakeError::InvalidIntroSpect` if the intro spect fails validation, see `IntroSpect::validate`
+    /// * `HandshakeError::MissingFlag` if neither -introspect nor -configure is present
+    /// * `HandshakeError::MissingValue` if -configure is present without a value
+    /// * `HandshakeError::MalformedJson` if the configure spec can't be deserialized
+    pub fn from_args<I, S>(args: I, intro_spect: &IntroSpect) -> Result<Self, HandshakeError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        intro_spect.validate()?;
+
+        let args: Vec<String> = args.into_iter().map(Into::into).collect();
+        let dev_mode = args.iter().any(|arg| arg == "-dev")
+            || std::env::var(DEV_MODE_ENV).is_ok_and(|value| is_truthy(&value));
+        match serve_intro_spect(&args, intro_spect) {
+            Ok(intro_spect_json) => Ok(Self::Introspect(intro_spect_json)),
+            Err(HandshakeError::MissingFlag(_)) => match recv_configuration_spec(args) {
+                Err(HandshakeError::MissingFlag(_)) if dev_mode => {
+                    dev_configuration_spec().map(Self::Configure)
+                }
+                result => result.map(Self::Configure),
+            },
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// Ask the OS for a port on the loopback interface that is currently free
+pub(crate) fn free_port() -> std::io::Result<u16> {
+    std::net::TcpListener::bind(("127.0.0.1", 0))
+        .and_then(|listener| listener.local_addr())
+        .map(|addr| addr.port())
+}
+
+fn is_truthy(value: &str) -> bool {
+    !matches!(value.trim(), "" | "0" | "false")
+}
+
+/// `DevConfigurationSpec` Function
+///
+/// This function will synthesize a `ConfigureSpec` for running the plugin outside Zoraxy,
+/// configured by the following environment variables:
+/// * `ZORAXY_DEV_PORT` - Port to listen on, defaults to a free port
+/// * `ZORAXY_DEV_API_KEY` - API key for accessing Zoraxy APIs, defaults to none
+/// * `ZORAXY_DEV_ZORAXY_PORT` - Port Zoraxy is running on, defaults to none
+/// * `ZORAXY_DEV_ZORAXY_VERSION` - Zoraxy version, defaults to `DEV_ZORAXY_VERSION`
+///
+/// The runtime constants always report a development build.
+///
+/// # Errors
+/// * This function will return an error if a port environment variable is not a valid port
+/// * This function will return an error if no free port can be found
+pub fn dev_configuration_spec() -> Result<ConfigureSpec, HandshakeError> {
+    dev_configuration_spec_from(|name| std::env::var(name).ok())
+}
+
+fn dev_configuration_spec_from(
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<ConfigureSpec, HandshakeError> {
+    let parse_port = |name: &'static str| {
+        env(name)
+            .map(|value| {
+                value
+                    .trim()
+                    .parse::<u16>()
+                    .map_err(|_| HandshakeError::InvalidEnvVar { name, value })
+            })
+            .transpose()
+    };
+
+    let port = match parse_port(DEV_PORT_ENV)? {
+        Some(port) => port,
+        None => free_port().map_err(HandshakeError::NoFreePort)?,
+    };
+
+    Ok(ConfigureSpec {
+        port,
+        runtime_constants: RuntimeConstants {
+            zoraxy_version: env(DEV_ZORAXY_VERSION_ENV)
+                .unwrap_or_else(|| DEV_ZORAXY_VERSION.to_string()),
+            zoraxy_uuid: "00000000-0000-0000-0000-000000000000".to_string(),
+            development_build: true,
+        },
+        api_key: env(DEV_API_KEY_ENV),
+        zoraxy_port: parse_port(DEV_ZORAXY_PORT_ENV)?,
+    })
+}
 
 /// `RecvExecuteConfigureSpec` Function
 ///
@@ -7,31 +174,53 @@ use anyhow::{Result, bail};
 ///
 /// Place this function after `ServeIntroSpect` function in your plugin main function
 ///
+/// The spec can also be read from a file by passing `-configure @path/to/spec.json`
+///
 /// # Arguments
 /// * `args` - A vector of strings representing the command line arguments
 ///
 /// # Returns
-/// * `Result<crate::types::ConfigureSpec>` - The `ConfigureSpec` object wrapped in a Result
+/// * `Result<ConfigureSpec, HandshakeError>` - The `ConfigureSpec` object wrapped in a Result
 ///
 /// # Errors
-/// * This function will return an error if -configure flag is not present
+/// * This function will return an error if -configure flag is not present, or has no value
+/// * This function will return an error if the referenced spec file can't be read
 /// * This function will return an error if deserialization of `ConfigureSpec` fails
-pub(crate) fn recv_configuration_spec(args: Vec<String>) -> Result<crate::types::ConfigureSpec> {
+pub(crate) fn recv_configuration_spec(args: Vec<String>) -> Result<ConfigureSpec, HandshakeError> {
     for (i, arg) in args.iter().enumerate() {
         if arg == "-configure" {
             let Some(spec_json) = args.into_iter().nth(i + 1) else {
-                bail!("No configure spec provided after -configure");
+                return Err(HandshakeError::MissingValue("-configure"));
             };
-            let spec: crate::types::ConfigureSpec = serde_json::from_str(&spec_json)?;
-            return Ok(spec);
-        } else if arg.starts_with("-configure=") {
-            let spec_json = arg.trim_start_matches("-configure=");
-            let spec: crate::types::ConfigureSpec = serde_json::from_str(spec_json)?;
-            return Ok(spec);
+            return parse_configure_spec(&spec_json);
+        } else if let Some(spec_json) = arg.strip_prefix("-configure=") {
+            return parse_configure_spec(spec_json);
         }
     }
 
-    bail!("no -configure flag found");
+    Err(HandshakeError::MissingFlag("-configure"))
+}
+
+fn parse_configure_spec(spec_json: &str) -> Result<ConfigureSpec, HandshakeError> {
+    // `@` is only resolved once, the file contents are always parsed as JSON
+    if let Some(path) = spec_json.strip_prefix('@') {
+        let spec_json =
+            std::fs::read_to_string(path).map_err(|source| HandshakeError::SpecFile {
+                path: path.to_string(),
+                source,
+            })?;
+        return deserialize_configure_spec(&spec_json);
+    }
+    deserialize_configure_spec(spec_json)
+}
+
+fn deserialize_configure_spec(spec_json: &str) -> Result<ConfigureSpec, HandshakeError> {
+    let deserializer = &mut serde_json::Deserializer::from_str(spec_json);
+    serde_path_to_error::deserialize(deserializer).map_err(|err| HandshakeError::MalformedJson {
+        flag: "-configure",
+        path: err.path().to_string(),
+        source: err.into_inner(),
+    })
 }
 
 /// `ServeIntroSpect` Function
@@ -46,53 +235,47 @@ pub(crate) fn recv_configuration_spec(args: Vec<String>) -> Result<crate::types:
 /// * `intro_spect` - A reference to the `IntroSpect` object to be printed
 ///
 /// # Returns
-/// * `Result<String>` - The intro spect as a JSON string if -introspect flag is present, otherwise Err
+/// * `Result<String, HandshakeError>` - The intro spect as a JSON string if -introspect flag is present, otherwise Err
 ///
 /// # Errors
 /// * This function will return an error if -introspect flag is not present
-///
-/// # Panics
-/// * This function will panic if serialization of `intro_spect` fails
+/// * This function will return an error if serialization of `intro_spect` fails
 pub(crate) fn serve_intro_spect(
     args: &[String],
-    intro_spect: &crate::types::IntroSpect,
-) -> Result<String> {
+    intro_spect: &IntroSpect,
+) -> Result<String, HandshakeError> {
     if let Some(arg) = args.get(1)
         && arg == "-introspect"
     {
-        let intro_spect_json =
-            serde_json::to_string_pretty(intro_spect).expect("Failed to serialize IntroSpect");
-        Ok(intro_spect_json)
+        serde_json::to_string_pretty(intro_spect).map_err(HandshakeError::SerializeIntroSpect)
     } else {
-        bail!("no -introspect flag found");
+        Err(HandshakeError::MissingFlag("-introspect"))
     }
 }
 
 /// `ServeAndRecvSpec` Function
 ///
 /// This function will serve the intro spect and return the configure spec
-/// See the `ServeIntroSpect` and `RecvConfigureSpec` for more details
+/// See `Handshake::from_args` for a version that doesn't exit the process
 ///
 /// # Arguments
 /// * `args` - A vector of strings representing the command line arguments
 /// * `intro_spect` - A reference to the `IntroSpect` object to be printed
 /// # Returns
-/// * `Result<crate::types::ConfigureSpec>` - The `ConfigureSpec` object  wrapped in a Result
+/// * `Result<ConfigureSpec>` - The `ConfigureSpec` object  wrapped in a Result
 /// # Errors
+/// * This function will return an error if the intro spect fails validation, see `IntroSpect::validate`
 /// * This function will return an error if receiving the configure spec fails
 /// # Exits
 /// * This function will exit the process if it serves the intro spect
-pub fn serve_and_recv_spec(
-    args: Vec<String>,
-    intro_spect: &crate::types::IntroSpect,
-) -> Result<crate::types::ConfigureSpec> {
-    serve_intro_spect(&args, intro_spect).map_or_else(
-        |_| recv_configuration_spec(args),
-        |intro_spect_json| {
+pub fn serve_and_recv_spec(args: Vec<String>, intro_spect: &IntroSpect) -> Result<ConfigureSpec> {
+    match Handshake::from_args(args, intro_spect)? {
+        Handshake::Configure(spec) => Ok(spec),
+        Handshake::Introspect(intro_spect_json) => {
             println!("{intro_spect_json}");
             std::process::exit(0);
-        },
-    )
+        }
+    }
 }
 
 #[cfg(test)]
@@ -178,4 +361,126 @@ mod tests {
         assert_eq!(config_spec.api_key.unwrap(), "my_api_key");
         assert_eq!(config_spec.zoraxy_port.unwrap(), 9090);
     }
+
+    #[test]
+    fn test_handshake_introspect() {
+        let handshake = Handshake::from_args(["plugin", "-introspect"], &helloworld_intro_spect());
+
+        assert!(
+            matches!(handshake, Ok(Handshake::Introspect(json)) if json == helloworld_expected_json())
+        );
+    }
+
+    #[test]
+    fn test_handshake_configure() {
+        let spec = r#"{"port":8080,"runtime_const":{"zoraxy_version":"3.2.9","zoraxy_uuid":"x","development_build":false}}"#;
+        let handshake = Handshake::from_args(
+            ["plugin".to_string(), format!("-configure={spec}")],
+            &helloworld_intro_spect(),
+        );
+
+        assert!(matches!(handshake, Ok(Handshake::Configure(spec)) if spec.port == 8080));
+    }
+
+    #[test]
+    fn test_handshake_errors() {
+        let intro_spect = helloworld_intro_spect();
+
+        assert!(matches!(
+            Handshake::from_args(["plugin"], &intro_spect),
+            Err(HandshakeError::MissingFlag("-configure"))
+        ));
+        assert!(matches!(
+            Handshake::from_args(["plugin", "-configure"], &intro_spect),
+            Err(HandshakeError::MissingValue("-configure"))
+        ));
+
+        let spec = r#"{"port":8080,"runtime_const":{"zoraxy_version":3}}"#;
+        let Err(HandshakeError::MalformedJson { path, .. }) =
+            Handshake::from_args(["plugin", "-configure", spec], &intro_spect)
+        else {
+            panic!("expected malformed JSON error");
+        };
+        assert_eq!(path, "runtime_const.zoraxy_version");
+    }
+
+    #[test]
+    fn test_recv_configuration_spec_from_file() {
+        let path = std::env::temp_dir().join(format!(
+            "zoraxy-rs-test-configure-spec-{}.json",
+            std::process::id()
+        ));
+        std::fs::write(
+            &path,
+            r#"{"port":8081,"runtime_const":{"zoraxy_version":"3.2.9","zoraxy_uuid":"x","development_build":true}}"#,
+        )
+        .unwrap();
+
+        let args = vec![
+            "plugin".to_string(),
+            "-configure".to_string(),
+            format!("@{}", path.display()),
+        ];
+        let config_spec = recv_configuration_spec(args).unwrap();
+        assert_eq!(config_spec.port, 8081);
+
+        // a file that points to itself is parsed as JSON, not followed again
+        std::fs::write(&path, format!("@{}", path.display())).unwrap();
+        let args = vec![
+            "plugin".to_string(),
+            format!("-configure=@{}", path.display()),
+        ];
+        let result = recv_configuration_spec(args);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(HandshakeError::MalformedJson { .. })));
+
+        let args = vec![
+            "plugin".to_string(),
+            "-configure=@/does/not/exist".to_string(),
+        ];
+        assert!(matches!(
+            recv_configuration_spec(args),
+            Err(HandshakeError::SpecFile { .. })
+        ));
+    }
+
+    #[test]
+    fn test_dev_configuration_spec() {
+        let config_spec = dev_configuration_spec_from(|name| match name {
+            DEV_API_KEY_ENV => Some("dev-key".to_string()),
+            DEV_ZORAXY_PORT_ENV => Some("8000".to_string()),
+            _ => None,
+        })
+        .unwrap();
+
+        assert_ne!(config_spec.port, 0);
+        assert!(config_spec.runtime_constants.development_build);
+        assert_eq!(
+            config_spec.runtime_constants.zoraxy_version,
+            DEV_ZORAXY_VERSION
+        );
+        assert_eq!(config_spec.api_key.as_deref(), Some("dev-key"));
+        assert_eq!(config_spec.zoraxy_port, Some(8000));
+
+        let invalid = dev_configuration_spec_from(|name| {
+            (name == DEV_PORT_ENV).then(|| "not-a-port".to_string())
+        });
+        assert!(matches!(
+            invalid,
+            Err(HandshakeError::InvalidEnvVar {
+                name: DEV_PORT_ENV,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_handshake_dev_flag() {
+        let handshake = Handshake::from_args(["plugin", "-dev"], &helloworld_intro_spect());
+
+        assert!(matches!(
+            handshake,
+            Ok(Handshake::Configure(spec)) if spec.runtime_constants.development_build
+        ));
+    }
 }
@@ -0,0 +1,1055 @@
+use std::convert::Infallible;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use arc_swap::ArcSwap;
+use axum::Router;
+use axum::body::Body;
+use axum::handler::HandlerWithoutStateExt;
+use axum::handler::future::IntoServiceFuture;
+use axum::http::{Method, Request, StatusCode, Uri, header};
+use axum::response::{IntoResponse, Response};
+use tower::util::BoxCloneSyncService;
+use tower::{Layer, Service};
+use tracing::{debug, warn};
+
+use crate::context::ZoraxyRequestContext;
+use crate::metrics::{CaptureMetrics, DEFAULT_HANDLER_LABEL};
+use crate::types::{ControlStatusCode, StaticCaptureSettings};
+
+mod params;
+mod pattern;
+
+pub use params::{CaptureParams, MissingCaptureParams};
+pub use pattern::{CapturePattern, CapturePatternError};
+
+pub(crate) const CAPTURE_HEADER: &str = "x-zoraxy-capture";
+pub(crate) const ORIGINAL_URI_HEADER: &str = "x-zoraxy-uri";
+
+type BoxedCaptureService = BoxCloneSyncService<Request<Body>, Response, Infallible>;
+
+/// A type-erased `tower::Layer` over capture services
+type BoxedLayer = Arc<dyn Fn(BoxedCaptureService) -> BoxedCaptureService + Send + Sync>;
+
+fn box_layer<L>(layer: L) -> BoxedLayer
+where
+    L: Layer<BoxedCaptureService> + Send + Sync + 'static,
+    L::Service: Service<Request<Body>, Response = Response, Error = Infallible>
+        + Send
+        + Sync
+        + Clone
+        + 'static,
+    <L::Service as Service<Request<Body>>>::Future: Send + 'static,
+{
+    Arc::new(move |service| BoxCloneSyncService::new(layer.layer(service)))
+}
+
+/// The handler registered for a capture pattern
+#[derive(Clone)]
+enum CaptureHandler {
+    /// Handles every method
+    Any(BoxedCaptureService),
+    /// Handles the listed methods, other methods get a 405
+    Methods(Vec<(Method, BoxedCaptureService)>),
+}
+
+impl CaptureHandler {
+    fn map(self, f: impl Fn(BoxedCaptureService) -> BoxedCaptureService) -> Self {
+        match self {
+            Self::Any(handler) => Self::Any(f(handler)),
+            Self::Methods(methods) => Self::Methods(
+                methods
+                    .into_iter()
+                    .map(|(method, handler)| (method, f(handler)))
+                    .collect(),
+            ),
+        }
+    }
+
+    async fn call(&self, req: Request<Body>) -> Result<Response, Infallible> {
+        let methods = match self {
+            Self::Any(handler) => return handler.clone().call(req).await,
+            Self::Methods(methods) => methods,
+        };
+
+        let find = |method: &Method| methods.iter().find(|(registered, _)| registered == method);
+        // like axum, HEAD requests are served by the GET handler unless HEAD has one of its own
+        let handler = match find(req.method()) {
+            None if req.method() == Method::HEAD => find(&Method::GET),
+            handler => handler,
+        };
+        match handler {
+            Some((_, handler)) => handler.clone().call(req).await,
+            None => Ok(method_not_allowed(methods)),
+        }
+    }
+}
+
+fn method_not_allowed(methods: &[(Method, BoxedCaptureService)]) -> Response {
+    let mut allowed: Vec<&str> = methods.iter().map(|(method, _)| method.as_str()).collect();
+    if allowed.contains(&"GET") && !allowed.contains(&"HEAD") {
+        allowed.push("HEAD");
+    }
+    (
+        StatusCode::METHOD_NOT_ALLOWED,
+        [(header::ALLOW, allowed.join(", "))],
+    )
+        .into_response()
+}
+
+type HandlerTable = Vec<(CapturePattern, CaptureHandler)>;
+
+/// Router that mimics the Go plugin static path capture behavior atop Axum services.
+///
+/// Handlers are registered for [`CapturePattern`]s, see there for how a capture path is matched
+/// and which handler wins when several patterns match.
+///
+/// Handlers can be registered and removed while serving, through the `Arc` shared with the
+/// [`StaticCaptureService`]: the handler table is an immutable snapshot that is swapped on every change,
+/// so dispatch never waits on a lock and requests already being dispatched finish with the snapshot they started with.
+pub struct StaticPathRouter {
+    handlers: ArcSwap<HandlerTable>,
+    default_handler: BoxedCaptureService,
+    layers: Vec<BoxedLayer>,
+    malformed_header_policy: MalformedHeaderPolicy,
+    metrics: Option<Arc<CaptureMetrics>>,
+    debug_enabled: AtomicBool,
+}
+
+impl Default for StaticPathRouter {
+    fn default() -> Self {
+        async fn default_handler(_req: Request<Body>) -> impl IntoResponse {
+            (
+                axum::http::StatusCode::NOT_FOUND,
+                "No capture handler registered",
+            )
+                .into_response()
+        }
+
+        #[derive(Clone)]
+        struct DefaultHandlerService;
+
+        impl Service<Request<Body>> for DefaultHandlerService {
+            type Response = Response;
+            type Error = Infallible;
+            type Future = IntoServiceFuture<Pin<Box<dyn Future<Output = Response> + Send>>>;
+
+            fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+                Poll::Ready(Ok(()))
+            }
+
+            fn call(&mut self, req: Request<Body>) -> Self::Future {
+                default_handler.into_service().call(req)
+            }
+        }
+
+        Self::new(DefaultHandlerService)
+    }
+}
+
+impl StaticPathRouter {
+    pub fn new<H>(default_handler: H) -> Self
+    where
+        H: Service<Request<Body>, Response = Response, Error = Infallible>
+            + Send
+            + Sync
+            + Clone
+            + 'static,
+        H::Future: Send + 'static,
+    {
+        Self {
+            handlers: ArcSwap::from_pointee(Vec::new()),
+            default_handler: BoxCloneSyncService::new(default_handler),
+            layers: Vec::new(),
+            malformed_header_policy: MalformedHeaderPolicy::default(),
+            metrics: None,
+            debug_enabled: AtomicBool::new(false),
+        }
+    }
+
+    /// Apply a `tower::Layer` to every handler, registered before or after this call, and to the default handler
+    ///
+    /// Like axum's `Router::layer`, each handler is wrapped separately, so stateful middleware
+    /// such as a rate limit is applied per handler. Layers added later wrap the earlier ones.
+    /// The layered services must not fail, handle errors of fallible middleware
+    /// with e.g. axum's `HandleErrorLayer`.
+    #[must_use]
+    pub fn layer<L>(mut self, layer: L) -> Self
+    where
+        L: Layer<BoxedCaptureService> + Send + Sync + 'static,
+        L::Service: Service<Request<Body>, Response = Response, Error = Infallible>
+            + Send
+            + Sync
+            + Clone
+            + 'static,
+        <L::Service as Service<Request<Body>>>::Future: Send + 'static,
+    {
+        let layer = box_layer(layer);
+        self.default_handler = layer(self.default_handler);
+        self.update_handlers(|handlers| {
+            for (_, handler) in handlers.iter_mut() {
+                *handler = handler.clone().map(|service| layer(service));
+            }
+        });
+        self.layers.push(layer);
+        self
+    }
+
+    /// Set how requests with malformed Zoraxy headers are handled, defaults to [`MalformedHeaderPolicy::Lenient`]
+    #[must_use]
+    pub fn with_malformed_header_policy(mut self, policy: MalformedHeaderPolicy) -> Self {
+        self.malformed_header_policy = policy;
+        self
+    }
+
+    /// Record the dispatched requests and their handler latency in `metrics`
+    #[must_use]
+    pub fn with_metrics(mut self, metrics: Arc<CaptureMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Wrap a handler in the layers added with [`Self::layer`]
+    fn apply_layers(&self, handler: BoxedCaptureService) -> BoxedCaptureService {
+        self.layers
+            .iter()
+            .fold(handler, |handler, layer| layer(handler))
+    }
+
+    /// Register `handler` for the capture paths matching `pattern`,
+    /// replacing the handler(s) previously registered for the same pattern
+    ///
+    /// `handler` serves every method, use [`Self::register_path_method_service`] or
+    /// an axum `MethodRouter` to handle methods separately.
+    ///
+    /// # Panics
+    /// * Panics if `pattern` is not a valid [`CapturePattern`], like axum's `Router::route` does for invalid paths
+    pub fn register_path_service<H>(&self, pattern: impl AsRef<str>, handler: H)
+    where
+        H: Service<Request<Body>, Response = Response, Error = Infallible>
+            + Send
+            + Sync
+            + Clone
+            + 'static,
+        H::Future: Send + 'static,
+    {
+        let pattern = parse_pattern(pattern.as_ref());
+        let handler = CaptureHandler::Any(self.apply_layers(BoxCloneSyncService::new(handler)));
+        self.update_handlers(|handlers| {
+            match handlers
+                .iter_mut()
+                .find(|(registered, _)| *registered == pattern)
+            {
+                Some((_, registered)) => *registered = handler.clone(),
+                None => handlers.push((pattern.clone(), handler.clone())),
+            }
+        });
+    }
+
+    /// Register an axum `Router` with its state for the capture paths matching `pattern`,
+    /// see [`Self::register_path_service`]
+    ///
+    /// The router sees the original request URI, so its routes are the full paths below the capture path,
+    /// e.g. `/api/users/{id}` for a router registered for `/api/*`.
+    ///
+    /// # Panics
+    /// * Panics if `pattern` is not a valid [`CapturePattern`]
+    pub fn register_path_router<S>(&self, pattern: impl AsRef<str>, router: Router<S>, state: S)
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        self.register_path_service(pattern, router.with_state(state));
+    }
+
+    /// Register `handler` wrapped in `layer` for the capture paths matching `pattern`,
+    /// see [`Self::register_path_service`]
+    ///
+    /// The layer only applies to this handler, inside the layers added with [`Self::layer`].
+    ///
+    /// # Panics
+    /// * Panics if `pattern` is not a valid [`CapturePattern`]
+    pub fn register_layered_path_service<H, L>(
+        &self,
+        pattern: impl AsRef<str>,
+        layer: L,
+        handler: H,
+    ) where
+        H: Service<Request<Body>, Response = Response, Error = Infallible>
+            + Send
+            + Sync
+            + Clone
+            + 'static,
+        H::Future: Send + 'static,
+        L: Layer<BoxedCaptureService> + Send + Sync + 'static,
+        L::Service: Service<Request<Body>, Response = Response, Error = Infallible>
+            + Send
+            + Sync
+            + Clone
+            + 'static,
+        <L::Service as Service<Request<Body>>>::Future: Send + 'static,
+    {
+        self.register_path_service(pattern, layer.layer(BoxCloneSyncService::new(handler)));
+    }
+
+    /// Register `handler` for `method` requests to the capture paths matching `pattern`
+    ///
+    /// Requests with a method that has no handler get a 405 with an `Allow` header listing the registered methods,
+    /// HEAD requests are served by the GET handler unless HEAD is registered too.
+    /// This replaces a handler registered with [`Self::register_path_service`] for the same pattern.
+    ///
+    /// # Panics
+    /// * Panics if `pattern` is not a valid [`CapturePattern`]
+    pub fn register_path_method_service<H>(
+        &self,
+        pattern: impl AsRef<str>,
+        method: &Method,
+        handler: H,
+    ) where
+        H: Service<Request<Body>, Response = Response, Error = Infallible>
+            + Send
+            + Sync
+            + Clone
+            + 'static,
+        H::Future: Send + 'static,
+    {
+        let pattern = parse_pattern(pattern.as_ref());
+        let handler = self.apply_layers(BoxCloneSyncService::new(handler));
+        self.update_handlers(|handlers| {
+            let entry = handlers
+                .iter_mut()
+                .find(|(registered, _)| *registered == pattern);
+            let methods = vec![(method.clone(), handler.clone())];
+
+            match entry {
+                Some((_, CaptureHandler::Methods(registered_methods))) => {
+                    match registered_methods
+                        .iter_mut()
+                        .find(|(registered, _)| registered == method)
+                    {
+                        Some((_, registered)) => *registered = handler.clone(),
+                        None => registered_methods.extend(methods),
+                    }
+                }
+                Some((_, registered)) => *registered = CaptureHandler::Methods(methods),
+                None => handlers.push((pattern.clone(), CaptureHandler::Methods(methods))),
+            }
+        });
+    }
+
+    /// Remove the handler(s) registered for `pattern`, an invalid pattern has no handlers to remove
+    pub fn remove_path_handler(&self, pattern: impl AsRef<str>) {
+        let Ok(pattern) = CapturePattern::new(pattern.as_ref()) else {
+            return;
+        };
+        self.update_handlers(|handlers| handlers.retain(|(registered, _)| *registered != pattern));
+    }
+
+    /// Swap in a modified copy of the handler table, `update` may be called again if another update raced it
+    fn update_handlers(&self, update: impl Fn(&mut HandlerTable)) {
+        self.handlers.rcu(|handlers| {
+            let mut handlers = HandlerTable::clone(handlers);
+            update(&mut handlers);
+            handlers
+        });
+    }
+
+    /// Find the handler for a normalized capture path and the path of the original request URI,
+    /// the most specific matching pattern wins
+    ///
+    /// Patterns with named segments are matched against the request path. If one of them matches,
+    /// the other patterns are matched against the request path too, so all candidates are ranked
+    /// against the same path, otherwise `/tenants` would match the capture path of `/tenants/42`
+    /// and always beat `/tenants/{id}`.
+    fn match_capture_path<'a>(
+        handlers: &'a HandlerTable,
+        capture_path: &str,
+        request_path: &str,
+    ) -> Option<(&'a (CapturePattern, CaptureHandler), CaptureParams)> {
+        let param_matched = handlers
+            .iter()
+            .any(|(pattern, _)| pattern.has_params() && pattern.matches(request_path));
+        handlers
+            .iter()
+            .filter_map(|entry| {
+                let path = if param_matched || entry.0.has_params() {
+                    request_path
+                } else {
+                    capture_path
+                };
+                entry
+                    .0
+                    .match_path(path)
+                    .map(|params| (entry, CaptureParams::new(params)))
+            })
+            // `max_by` returns the last of equal elements, reverse so the first registered wins ties
+            .rev()
+            .max_by(|((a, _), _), ((b, _), _)| a.cmp_specificity(b))
+    }
+
+    /// Generate the `StaticCaptureSettings` advertising the capture paths of the registered patterns,
+    /// glob patterns can't be advertised and are left out, see [`CapturePattern::capture_path`]
+    #[must_use]
+    pub fn static_capture_settings(&self, ingress: impl AsRef<str>) -> StaticCaptureSettings {
+        let mut capture_paths: Vec<String> = Vec::new();
+        for (pattern, _) in self.handlers.load().iter() {
+            if let Some(capture_path) = pattern.capture_path()
+                && !capture_paths.contains(&capture_path)
+            {
+                capture_paths.push(capture_path);
+            }
+        }
+
+        capture_paths
+            .into_iter()
+            .fold(StaticCaptureSettings::new(ingress), |settings, path| {
+                settings.add_static_capture_path(path)
+            })
+    }
+
+    /// Check that the capture paths advertised in `settings` agree with the registered patterns,
+    /// logging a warning for every mismatch found
+    pub fn check_capture_settings(
+        &self,
+        settings: &StaticCaptureSettings,
+    ) -> Vec<CaptureSettingsMismatch> {
+        let capture_paths: Vec<String> = settings
+            .static_capture_paths()
+            .iter()
+            .map(|rule| normalize_capture_path(rule.capture_path()))
+            .collect();
+        let handlers = self.handlers.load();
+
+        let unhandled = capture_paths
+            .iter()
+            .filter(|path| {
+                !handlers
+                    .iter()
+                    .any(|(pattern, _)| pattern.handles_capture_path(path))
+            })
+            .map(|path| CaptureSettingsMismatch::UnhandledCapturePath(path.clone()));
+        let unadvertised = handlers
+            .iter()
+            .filter(|(pattern, _)| {
+                !capture_paths
+                    .iter()
+                    .any(|path| pattern.handles_capture_path(path))
+            })
+            .map(|(pattern, _)| CaptureSettingsMismatch::UnadvertisedPattern(pattern.clone()));
+
+        let mismatches: Vec<_> = unhandled.chain(unadvertised).collect();
+        for mismatch in &mismatches {
+            warn!(target: "zoraxy::static_router", "{mismatch}");
+        }
+        mismatches
+    }
+
+    pub fn set_debug_print_mode(&self, enable: bool) {
+        self.debug_enabled.store(enable, Ordering::Relaxed);
+    }
+
+    pub fn debug_enabled(&self) -> bool {
+        self.debug_enabled.load(Ordering::Relaxed)
+    }
+
+    fn log_capture_path(&self, capture_path: &str) {
+        if self.debug_enabled() {
+            debug!(target: "zoraxy::static_router", capture_path, "Using capture path");
+        }
+    }
+
+    pub(crate) async fn dispatch_capture(
+        &self,
+        mut req: Request<Body>,
+    ) -> Result<Response, Infallible> {
+        let capture_path = if let Ok(capture_path) = header_value(req.headers().get(CAPTURE_HEADER))
+        {
+            capture_path
+        } else {
+            let malformed = MalformedCaptureHeader::InvalidCapturePath;
+            if let Some(resp) = self.malformed_header_policy.respond(&malformed) {
+                return Ok(resp);
+            }
+            None
+        };
+
+        let capture_label = capture_path
+            .as_deref()
+            .map(normalize_capture_path)
+            .unwrap_or_default();
+
+        if let Some(capture_path) = capture_path {
+            let normalized_path = normalize_capture_path(&capture_path);
+            self.log_capture_path(&normalized_path);
+
+            let mut context = ZoraxyRequestContext::new(req.uri(), req.headers());
+            context.set_capture_path(normalized_path.clone());
+            req.extensions_mut().insert(context);
+
+            let rewritten = match header_value(req.headers().get(ORIGINAL_URI_HEADER)) {
+                Ok(Some(original_uri)) => rewrite_request_path(req.uri_mut(), &original_uri)
+                    .map_err(|source| MalformedCaptureHeader::InvalidOriginalUri {
+                        original_uri,
+                        source,
+                    }),
+                Ok(None) => Ok(()),
+                Err(()) => Err(MalformedCaptureHeader::InvalidOriginalUriEncoding),
+            };
+            if let Err(malformed) = rewritten
+                && let Some(resp) = self.malformed_header_policy.respond(&malformed)
+            {
+                return Ok(resp);
+            }
+
+            let matched = {
+                let handlers = self.handlers.load();
+                Self::match_capture_path(&handlers, &normalized_path, req.uri().path()).map(
+                    |((pattern, handler), params)| {
+                        if self.debug_enabled() {
+                            debug!(target: "zoraxy::static_router", %pattern, "Matched capture pattern");
+                        }
+                        (handler.clone(), params, pattern.to_string())
+                    },
+                )
+            };
+
+            if let Some((handler, params, pattern)) = matched {
+                req.extensions_mut().insert(params);
+                return self
+                    .record_dispatch(&capture_label, &pattern, handler.call(req))
+                    .await;
+            }
+        }
+
+        let mut default_handler = self.default_handler.clone();
+        self.record_dispatch(
+            &capture_label,
+            DEFAULT_HANDLER_LABEL,
+            default_handler.call(req),
+        )
+        .await
+    }
+
+    async fn record_dispatch(
+        &self,
+        capture_path: &str,
+        pattern: &str,
+        response: impl Future<Output = Result<Response, Infallible>>,
+    ) -> Result<Response, Infallible> {
+        let start = Instant::now();
+        let response = response.await;
+        if let Some(metrics) = &self.metrics {
+            metrics.record_static_dispatch(capture_path, pattern, start.elapsed());
+        }
+        response
+    }
+
+    pub const fn into_capture_service(self: Arc<Self>) -> StaticCaptureService {
+        StaticCaptureService::new(self)
+    }
+}
+
+/// A disagreement between the advertised `StaticCaptureSettings` and a `StaticPathRouter`,
+/// see [`StaticPathRouter::check_capture_settings`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaptureSettingsMismatch {
+    /// An advertised capture path no pattern matches, its traffic goes to the default handler
+    UnhandledCapturePath(String),
+    /// A registered pattern matching none of the advertised capture paths, its handler is never called
+    UnadvertisedPattern(CapturePattern),
+}
+
+impl fmt::Display for CaptureSettingsMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnhandledCapturePath(path) => write!(
+                f,
+                "capture path {path} has no handler, its traffic goes to the default handler"
+            ),
+            Self::UnadvertisedPattern(pattern) => write!(
+                f,
+                "capture pattern {pattern} matches no advertised capture path, its handler is never called"
+            ),
+        }
+    }
+}
+
+/// Parse a pattern passed to a `register_*` method, panics if it is invalid
+fn parse_pattern(pattern: &str) -> CapturePattern {
+    CapturePattern::new(pattern).unwrap_or_else(|err| panic!("{err}"))
+}
+
+/// Read a header as a string, `Err` if it is not valid UTF-8
+fn header_value(value: Option<&axum::http::HeaderValue>) -> Result<Option<String>, ()> {
+    value
+        .map(|val| val.to_str().map(ToString::to_string).map_err(|_| ()))
+        .transpose()
+}
+
+/// A malformed Zoraxy header on a static capture request
+#[derive(Debug, thiserror::Error)]
+pub enum MalformedCaptureHeader {
+    #[error("{CAPTURE_HEADER} header is not valid UTF-8")]
+    InvalidCapturePath,
+    #[error("{ORIGINAL_URI_HEADER} header is not valid UTF-8")]
+    InvalidOriginalUriEncoding,
+    #[error("{ORIGINAL_URI_HEADER} header {original_uri:?} is not a valid URI: {source}")]
+    InvalidOriginalUri {
+        original_uri: String,
+        source: axum::http::Error,
+    },
+}
+
+/// Callback of [`MalformedHeaderPolicy::Custom`]
+pub type MalformedHeaderCallback =
+    Arc<dyn Fn(&MalformedCaptureHeader) -> Option<Response> + Send + Sync>;
+
+/// How `StaticPathRouter` handles capture requests with malformed Zoraxy headers
+#[derive(Clone, Default)]
+pub enum MalformedHeaderPolicy {
+    /// Log a warning and dispatch the request anyway:
+    /// a malformed capture path goes to the default handler, a malformed URI is not rewritten
+    #[default]
+    Lenient,
+    /// Respond with 400 Bad Request
+    Strict,
+    /// Respond with [`ControlStatusCode::Error`], so Zoraxy logs the failure and handles the request itself
+    ControlError,
+    /// Respond with the callback's response, or dispatch leniently if it returns `None`
+    Custom(MalformedHeaderCallback),
+}
+
+impl MalformedHeaderPolicy {
+    /// The response to send instead of dispatching the request, if any
+    fn respond(&self, malformed: &MalformedCaptureHeader) -> Option<Response> {
+        warn!(target: "zoraxy::static_router", %malformed, "Malformed capture request");
+        match self {
+            Self::Lenient => None,
+            Self::Strict => Some((StatusCode::BAD_REQUEST, malformed.to_string()).into_response()),
+            Self::ControlError => Some(ControlStatusCode::Error.into_response()),
+            Self::Custom(respond) => respond(malformed),
+        }
+    }
+}
+
+impl fmt::Debug for MalformedHeaderPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Lenient => f.write_str("Lenient"),
+            Self::Strict => f.write_str("Strict"),
+            Self::ControlError => f.write_str("ControlError"),
+            Self::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+fn normalize_capture_path(path: &str) -> String {
+    if path.len() > 1 && path.ends_with('/') {
+        path[..path.len() - 1].to_owned()
+    } else {
+        path.to_owned()
+    }
+}
+
+fn rewrite_request_path(uri: &mut Uri, new_path: &str) -> Result<(), axum::http::Error> {
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(new_path.parse()?);
+    *uri = Uri::from_parts(parts)?;
+    Ok(())
+}
+
+#[derive(Clone)]
+pub struct StaticCaptureService {
+    router: Arc<StaticPathRouter>,
+}
+
+impl StaticCaptureService {
+    pub const fn new(router: Arc<StaticPathRouter>) -> Self {
+        Self { router }
+    }
+}
+
+impl Service<Request<Body>> for StaticCaptureService {
+    type Response = Response;
+    type Error = Infallible;
+    type Future =
+        Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send + 'static>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let router = self.router.clone();
+        Box::pin(async move { router.dispatch_capture(req).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::StaticCaptureRule;
+    use axum::extract::{Path, State};
+    use axum::handler::HandlerWithoutStateExt;
+    use axum::http::HeaderValue;
+    use pretty_assertions::assert_eq;
+
+    async fn dispatch(router: &StaticPathRouter, capture_path: &str) -> String {
+        let req = Request::builder()
+            .uri("/capture")
+            .header(CAPTURE_HEADER, capture_path)
+            .header(ORIGINAL_URI_HEADER, capture_path)
+            .body(Body::empty())
+            .unwrap();
+        let resp = router.dispatch_capture(req).await.unwrap();
+        let body = axum::body::to_bytes(resp.into_body(), 1024).await.unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn dispatches_to_most_specific_pattern() {
+        let router = StaticPathRouter::default();
+        router.register_path_service("/api/*", (async || "api subtree").into_service());
+        router.register_path_service("/api/v*/users", (async || "users glob").into_service());
+        router.register_path_service("/api/v2/users", (async || "v2 users").into_service());
+        router.register_path_service("/x*/*", (async || "first").into_service());
+        router.register_path_service("/*y/*", (async || "second").into_service());
+
+        assert_eq!(dispatch(&router, "/api").await, "api subtree");
+        assert_eq!(dispatch(&router, "/api/v3").await, "api subtree");
+        assert_eq!(dispatch(&router, "/api/v3/users").await, "users glob");
+        assert_eq!(dispatch(&router, "/api/v2/users/").await, "v2 users");
+        assert_eq!(dispatch(&router, "/xay/z").await, "first");
+        assert_eq!(
+            dispatch(&router, "/other").await,
+            "No capture handler registered"
+        );
+
+        router.remove_path_handler("/api/*");
+        assert_eq!(
+            dispatch(&router, "/api/v3").await,
+            "No capture handler registered"
+        );
+    }
+
+    #[tokio::test]
+    async fn passes_named_segments_to_handler() {
+        async fn tenant_file(params: CaptureParams) -> String {
+            let values: Vec<String> = params
+                .iter()
+                .map(|(name, value)| format!("{name}={value}"))
+                .collect();
+            values.join(",")
+        }
+
+        let router = StaticPathRouter::default();
+        router.register_path_service("/tenants/*", (async || "tenants").into_service());
+        router.register_path_service(
+            "/tenants/{tenant}/files/{*path}",
+            tenant_file.into_service(),
+        );
+
+        let req = Request::builder()
+            .uri("/s_capture/")
+            .header(CAPTURE_HEADER, "/tenants")
+            .header(ORIGINAL_URI_HEADER, "/tenants/42/files/a.txt?raw=1")
+            .body(Body::empty())
+            .unwrap();
+        let resp = router.dispatch_capture(req).await.unwrap();
+        let body = axum::body::to_bytes(resp.into_body(), 1024).await.unwrap();
+        assert_eq!(body, "tenant=42,path=a.txt");
+
+        assert_eq!(dispatch(&router, "/tenants").await, "tenants");
+    }
+
+    #[tokio::test]
+    async fn ranks_exact_and_param_patterns_on_the_same_path() {
+        async fn tenant(params: CaptureParams) -> String {
+            format!("tenant {}", params.get("id").unwrap())
+        }
+
+        let router = StaticPathRouter::default();
+        router.register_path_service("/tenants", (async || "tenants").into_service());
+        router.register_path_service("/tenants/{id}", tenant.into_service());
+
+        let send = async |original_uri: &str| {
+            let req = Request::builder()
+                .uri("/s_capture/")
+                .header(CAPTURE_HEADER, "/tenants")
+                .header(ORIGINAL_URI_HEADER, original_uri)
+                .body(Body::empty())
+                .unwrap();
+            let resp = router.dispatch_capture(req).await.unwrap();
+            let body = axum::body::to_bytes(resp.into_body(), 1024).await.unwrap();
+            String::from_utf8(body.to_vec()).unwrap()
+        };
+
+        assert_eq!(send("/tenants/42").await, "tenant 42");
+        assert_eq!(send("/tenants").await, "tenants");
+        assert_eq!(send("/tenants/42/users").await, "tenants");
+    }
+
+    #[tokio::test]
+    async fn dispatches_by_method() {
+        let router = StaticPathRouter::default();
+        router.register_path_method_service(
+            "/items",
+            &Method::GET,
+            (async || "list").into_service(),
+        );
+        router.register_path_method_service(
+            "/items",
+            &Method::POST,
+            (async || "create").into_service(),
+        );
+        router.register_path_service(
+            "/users",
+            axum::routing::get(async || "users").delete(async || "deleted"),
+        );
+
+        let send = async |method: Method, capture_path: &str| {
+            let req = Request::builder()
+                .method(method)
+                .uri(capture_path)
+                .header(CAPTURE_HEADER, capture_path)
+                .body(Body::empty())
+                .unwrap();
+            router.dispatch_capture(req).await.unwrap()
+        };
+
+        let resp = send(Method::POST, "/items").await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), 1024).await.unwrap();
+        assert_eq!(body, "create");
+        assert_eq!(send(Method::HEAD, "/items").await.status(), StatusCode::OK);
+
+        let resp = send(Method::DELETE, "/items").await;
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(resp.headers()[header::ALLOW], "GET, POST, HEAD");
+
+        let resp = send(Method::PUT, "/users").await;
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert!(resp.headers().contains_key(header::ALLOW));
+    }
+
+    #[tokio::test]
+    async fn provides_request_context() {
+        async fn handler(ctx: ZoraxyRequestContext) -> String {
+            format!(
+                "{:?} {} {} {:?} {:?}",
+                ctx.capture_path(),
+                ctx.original_uri(),
+                ctx.ingress_uri(),
+                ctx.request_id(),
+                ctx.client_ip()
+            )
+        }
+
+        let router = StaticPathRouter::default();
+        router.register_path_service("/test_a/*", handler.into_service());
+
+        let req = Request::builder()
+            .uri("/s_capture/")
+            .header(CAPTURE_HEADER, "/test_a/")
+            .header(ORIGINAL_URI_HEADER, "/test_a/x?y=1")
+            .header(crate::dynamic_router::REQUEST_ID_HEADER, "abc123")
+            .header("x-forwarded-for", "203.0.113.7")
+            .body(Body::empty())
+            .unwrap();
+        let resp = router.dispatch_capture(req).await.unwrap();
+        let body = axum::body::to_bytes(resp.into_body(), 1024).await.unwrap();
+        assert_eq!(
+            body,
+            r#"Some("/test_a") /test_a/x?y=1 /s_capture/ Some("abc123") Some(203.0.113.7)"#
+        );
+    }
+
+    #[tokio::test]
+    async fn applies_global_and_per_route_layers() {
+        fn tag(value: &'static str) -> impl Fn(Response) -> std::future::Ready<Response> + Clone {
+            move |mut resp: Response| {
+                resp.headers_mut().append("x-layer", value.parse().unwrap());
+                std::future::ready(resp)
+            }
+        }
+
+        let router = StaticPathRouter::default();
+        router.register_path_service("/before", (async || "before").into_service());
+        let router = router.layer(axum::middleware::map_response(tag("global")));
+        router.register_layered_path_service(
+            "/after",
+            axum::middleware::map_response(tag("route")),
+            (async || "after").into_service(),
+        );
+
+        let layers = async |capture_path: &str| {
+            let req = Request::builder()
+                .uri(capture_path)
+                .header(CAPTURE_HEADER, capture_path)
+                .body(Body::empty())
+                .unwrap();
+            let resp = router.dispatch_capture(req).await.unwrap();
+            resp.headers()
+                .get_all("x-layer")
+                .iter()
+                .map(|value| value.to_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(layers("/before").await, ["global"]);
+        assert_eq!(layers("/after").await, ["route", "global"]);
+        assert_eq!(layers("/unknown").await, ["global"]);
+    }
+
+    #[tokio::test]
+    async fn applies_malformed_header_policy() {
+        let send =
+            async |policy: MalformedHeaderPolicy, capture_path: &[u8], original_uri: &str| {
+                let router = StaticPathRouter::default().with_malformed_header_policy(policy);
+                router.register_path_service("/test_a", (async || "a").into_service());
+                let req = Request::builder()
+                    .uri("/s_capture/")
+                    .header(
+                        CAPTURE_HEADER,
+                        HeaderValue::from_bytes(capture_path).unwrap(),
+                    )
+                    .header(ORIGINAL_URI_HEADER, original_uri)
+                    .body(Body::empty())
+                    .unwrap();
+                router.dispatch_capture(req).await.unwrap().status()
+            };
+
+        let bad_uri = "/test a";
+        assert_eq!(
+            send(MalformedHeaderPolicy::Lenient, b"/test_a", bad_uri).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            send(MalformedHeaderPolicy::Lenient, b"/test_\xff", "/test_a").await,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            send(MalformedHeaderPolicy::Strict, b"/test_a", bad_uri).await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            send(
+                MalformedHeaderPolicy::ControlError,
+                b"/test_\xff",
+                "/test_a"
+            )
+            .await,
+            ControlStatusCode::Error.status_code()
+        );
+        assert_eq!(
+            send(MalformedHeaderPolicy::Strict, b"/test_a", "/test_a?x=1").await,
+            StatusCode::OK
+        );
+
+        let custom = MalformedHeaderPolicy::Custom(Arc::new(|malformed| {
+            matches!(malformed, MalformedCaptureHeader::InvalidOriginalUri { .. })
+                .then(|| StatusCode::IM_A_TEAPOT.into_response())
+        }));
+        assert_eq!(
+            send(custom.clone(), b"/test_a", bad_uri).await,
+            StatusCode::IM_A_TEAPOT
+        );
+        assert_eq!(
+            send(custom, b"/test_\xff", "/test_a").await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn mounts_stateful_routers() {
+        #[derive(Clone)]
+        struct AppState {
+            greeting: &'static str,
+        }
+
+        let api = Router::new()
+            .route(
+                "/api/users/{id}",
+                axum::routing::get(async |State(state): State<AppState>, Path(id): Path<u32>| {
+                    format!("{} user {id}", state.greeting)
+                }),
+            )
+            .fallback(async || (StatusCode::NOT_FOUND, "no such api"));
+
+        let router = StaticPathRouter::default();
+        router.register_path_router("/api/*", api, AppState { greeting: "hello" });
+
+        let send = async |original_uri: &str| {
+            let req = Request::builder()
+                .uri("/s_capture/")
+                .header(CAPTURE_HEADER, "/api")
+                .header(ORIGINAL_URI_HEADER, original_uri)
+                .body(Body::empty())
+                .unwrap();
+            let resp = router.dispatch_capture(req).await.unwrap();
+            let body = axum::body::to_bytes(resp.into_body(), 1024).await.unwrap();
+            String::from_utf8(body.to_vec()).unwrap()
+        };
+
+        assert_eq!(send("/api/users/42").await, "hello user 42");
+        assert_eq!(send("/api/posts").await, "no such api");
+    }
+
+    #[tokio::test]
+    async fn registers_handlers_while_serving() {
+        let router = Arc::new(StaticPathRouter::default());
+        let mut service = router.clone().into_capture_service();
+        let send = async |service: &mut StaticCaptureService| {
+            let req = Request::builder()
+                .uri("/s_capture/")
+                .header(CAPTURE_HEADER, "/feature")
+                .body(Body::empty())
+                .unwrap();
+            service.call(req).await.unwrap().status()
+        };
+
+        assert_eq!(send(&mut service).await, StatusCode::NOT_FOUND);
+        router.register_path_service("/feature", (async || "enabled").into_service());
+        assert_eq!(send(&mut service).await, StatusCode::OK);
+        router.remove_path_handler("/feature");
+        assert_eq!(send(&mut service).await, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn derives_and_checks_capture_settings() {
+        let router = StaticPathRouter::default();
+        router.register_path_service("/test_a", (async || "a").into_service());
+        router.register_path_service("/test_b/*", (async || "b").into_service());
+        router.register_path_service("/test_c/v*", (async || "c").into_service());
+
+        let settings = router.static_capture_settings("/s_capture");
+        let paths: Vec<_> = settings
+            .static_capture_paths()
+            .iter()
+            .map(StaticCaptureRule::capture_path)
+            .collect();
+        assert_eq!(paths, ["/test_a", "/test_b"]);
+        assert_eq!(
+            router.check_capture_settings(&settings),
+            [CaptureSettingsMismatch::UnadvertisedPattern(
+                CapturePattern::new("/test_c/v*").unwrap()
+            )]
+        );
+
+        let settings = StaticCaptureSettings::new("/s_capture")
+            .add_static_capture_path("/test_a/")
+            .add_static_capture_path("/test_b/x")
+            .add_static_capture_path("/test_c/v2")
+            .add_static_capture_path("/tset_d");
+        assert_eq!(
+            router.check_capture_settings(&settings),
+            [CaptureSettingsMismatch::UnhandledCapturePath(
+                "/tset_d".to_string()
+            )]
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use axum::extract::FromRequestParts;
+use axum::http::StatusCode;
+use axum::http::request::Parts;
+use axum::response::{IntoResponse, Response};
+
+/// Values of the named segments of the [`CapturePattern`](super::CapturePattern) a request matched
+///
+/// Inserted by `StaticPathRouter` before calling the capture handler,
+/// empty if the pattern has no named segments.
+///
+/// ```
+/// use zoraxy_rs::prelude::*;
+///
+/// // registered for "/tenants/{tenant}/files/{*path}"
+/// async fn tenant_file(params: CaptureParams) -> String {
+///     format!(
+///         "tenant {} file {}",
+///         params.get("tenant").unwrap_or_default(),
+///         params.get("path").unwrap_or_default()
+///     )
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CaptureParams(Vec<(String, String)>);
+
+impl CaptureParams {
+    pub(crate) const fn new(params: Vec<(String, String)>) -> Self {
+        Self(params)
+    }
+
+    /// The percent-decoded value of the named segment `name`
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Iterate over the named segments and their values, in pattern order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Rejection for [`CaptureParams`] used outside a `StaticPathRouter` capture handler
+#[derive(Debug, thiserror::Error)]
+#[error("capture params are only available to StaticPathRouter capture handlers")]
+pub struct MissingCaptureParams;
+
+impl IntoResponse for MissingCaptureParams {
+    fn into_response(self) -> Response {
+        (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()).into_response()
+    }
+}
+
+impl<S> FromRequestParts<S> for CaptureParams
+where
+    S: Send + Sync,
+{
+    type Rejection = MissingCaptureParams;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Self>()
+            .cloned()
+            .ok_or(MissingCaptureParams)
+    }
+}
@@ -0,0 +1,443 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use globset::{GlobBuilder, GlobMatcher};
+
+/// A capture path pattern that a `StaticPathRouter` handler is registered for
+///
+/// * `/api` matches the capture path `/api` exactly
+/// * `/api/*` matches `/api` and every capture path below it, e.g. `/api/v2/users`
+/// * any other `*` matches any characters within a single path segment, `?` a single character
+///   and `[...]` one of a set of characters, e.g. `/api/v*/users` matches `/api/v2/users` but not `/api/v2/admin/users`
+/// * `{name}` matches a single path segment and `{*name}`, as the last segment, the rest of the path,
+///   their values are available to the handler through [`CaptureParams`](super::CaptureParams)
+///
+/// Patterns with named segments are matched against the path of the original request URI,
+/// as the capture path only contains the part advertised to Zoraxy, e.g. `/tenants` for `/tenants/{id}`.
+/// When one of them matches, the other patterns are matched against the request URI as well,
+/// so `/tenants` doesn't catch `/tenants/42` from `/tenants/{id}`.
+///
+/// When several patterns match a request the most specific one wins:
+/// 1. exact patterns
+/// 2. the pattern with the most literal (non-wildcard) characters
+/// 3. glob patterns over prefix patterns
+/// 4. the pattern registered first
+#[derive(Debug, Clone)]
+pub struct CapturePattern {
+    pattern: String,
+    segments: Vec<Segment>,
+    /// Whether the pattern ends with `/*` or `{*name}`, matching the whole subtree
+    subtree: bool,
+    /// Name of the trailing `{*name}` segment
+    catch_all: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    /// A literal segment
+    Literal(String),
+    /// A segment with `*`, `?` or `[...]`
+    Glob(GlobMatcher),
+    /// `{name}`, matching a single segment
+    Param(String),
+}
+
+/// Errors returned when parsing a [`CapturePattern`]
+#[derive(Debug, thiserror::Error)]
+pub enum CapturePatternError {
+    /// A `{*name}` segment is followed by other segments
+    #[error("catch-all segment {segment} must be the last segment of capture pattern {pattern}")]
+    CatchAllNotLast { pattern: String, segment: String },
+    /// A segment is not a valid glob, e.g. it has an unclosed `[`
+    #[error("invalid glob {glob:?} in capture pattern {pattern}: {source}")]
+    InvalidGlob {
+        pattern: String,
+        glob: String,
+        source: globset::Error,
+    },
+}
+
+impl CapturePattern {
+    /// Parse a capture path pattern, a trailing `/` is ignored like it is for capture paths
+    ///
+    /// # Errors
+    /// * Returns an error if a `{*name}` segment is not the last segment of the pattern
+    /// * Returns an error if a segment is not a valid glob
+    pub fn new(pattern: &str) -> Result<Self, CapturePatternError> {
+        let pattern = super::normalize_capture_path(pattern);
+        let parts: Vec<&str> = split_segments(&pattern).collect();
+
+        let mut segments = Vec::with_capacity(parts.len());
+        let mut subtree = false;
+        let mut catch_all = None;
+        for (i, part) in parts.iter().enumerate() {
+            let last = i + 1 == parts.len();
+            if let Some(name) = part.strip_prefix("{*").and_then(|p| p.strip_suffix('}')) {
+                if !last {
+                    return Err(CapturePatternError::CatchAllNotLast {
+                        pattern: pattern.clone(),
+                        segment: (*part).to_string(),
+                    });
+                }
+                subtree = true;
+                catch_all = Some(name.to_string());
+            } else if let Some(name) = part.strip_prefix('{').and_then(|p| p.strip_suffix('}')) {
+                segments.push(Segment::Param(name.to_string()));
+            } else if last && *part == "*" {
+                subtree = true;
+            } else if has_wildcard(part) {
+                let matcher = GlobBuilder::new(part)
+                    .literal_separator(true)
+                    .build()
+                    .map_err(|source| CapturePatternError::InvalidGlob {
+                        pattern: pattern.clone(),
+                        glob: (*part).to_string(),
+                        source,
+                    })?
+                    .compile_matcher();
+                segments.push(Segment::Glob(matcher));
+            } else {
+                segments.push(Segment::Literal((*part).to_string()));
+            }
+        }
+
+        Ok(Self {
+            pattern,
+            segments,
+            subtree,
+            catch_all,
+        })
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Whether the pattern matches a single capture path, without wildcards or named segments
+    #[must_use]
+    pub fn is_exact(&self) -> bool {
+        !self.subtree
+            && self
+                .segments
+                .iter()
+                .all(|segment| matches!(segment, Segment::Literal(_)))
+    }
+
+    /// Whether the pattern matches a whole subtree of paths, i.e. ends with `/*` or `{*name}`
+    #[must_use]
+    pub const fn is_prefix(&self) -> bool {
+        self.subtree
+    }
+
+    /// Whether the pattern has named segments, and is thus matched against the original request URI
+    #[must_use]
+    pub fn has_params(&self) -> bool {
+        self.catch_all.is_some()
+            || self
+                .segments
+                .iter()
+                .any(|segment| matches!(segment, Segment::Param(_)))
+    }
+
+    /// The capture path to advertise to Zoraxy for this pattern, `None` for glob patterns
+    ///
+    /// Zoraxy only sends advertised capture paths, so `/api/*` is advertised as `/api`
+    /// and `/tenants/{id}` as `/tenants`.
+    #[must_use]
+    pub fn capture_path(&self) -> Option<String> {
+        let mut capture_path = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Glob(_) => return None,
+                Segment::Literal(literal) => {
+                    capture_path.push('/');
+                    capture_path.push_str(literal);
+                }
+                Segment::Param(_) => break,
+            }
+        }
+        if capture_path.is_empty() {
+            capture_path.push('/');
+        }
+        Some(capture_path)
+    }
+
+    /// Whether the pattern matches the (normalized) path
+    #[must_use]
+    pub fn matches(&self, path: &str) -> bool {
+        self.match_path(path).is_some()
+    }
+
+    /// Match the path, returning the values of the named segments
+    pub(crate) fn match_path(&self, path: &str) -> Option<Vec<(String, String)>> {
+        let mut params = Vec::new();
+        let mut path_segments = split_segments(path);
+        for segment in &self.segments {
+            let path_segment = path_segments.next()?;
+            match segment {
+                Segment::Literal(literal) => {
+                    if literal != path_segment {
+                        return None;
+                    }
+                }
+                Segment::Glob(glob) => {
+                    if !glob.is_match(path_segment) {
+                        return None;
+                    }
+                }
+                Segment::Param(name) => params.push((name.clone(), decode(path_segment))),
+            }
+        }
+
+        if let Some(name) = &self.catch_all {
+            let rest: Vec<&str> = path_segments.collect();
+            params.push((name.clone(), decode(&rest.join("/"))));
+        } else if !self.subtree && path_segments.next().is_some() {
+            return None;
+        }
+        Some(params)
+    }
+
+    /// Whether requests for the advertised `capture_path` can reach this pattern
+    pub(crate) fn handles_capture_path(&self, capture_path: &str) -> bool {
+        if !self.has_params() {
+            return self.matches(capture_path);
+        }
+
+        // Zoraxy captures the whole subtree of an advertised path,
+        // so the pattern is reachable if either path is an ancestor of the other
+        self.capture_path().is_some_and(|own_path| {
+            let own: Vec<&str> = split_segments(&own_path).collect();
+            let advertised: Vec<&str> = split_segments(capture_path).collect();
+            own.iter().zip(&advertised).all(|(a, b)| a == b)
+        })
+    }
+
+    fn literal_len(&self) -> usize {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Literal(literal) => 1 + literal.chars().count(),
+                Segment::Glob(glob) => 1 + glob_literal_len(glob.glob().glob()),
+                Segment::Param(_) => 1,
+            })
+            .sum()
+    }
+
+    /// Compare how specific two patterns are, the greater one wins when both match
+    pub(crate) fn cmp_specificity(&self, other: &Self) -> Ordering {
+        self.is_exact()
+            .cmp(&other.is_exact())
+            .then_with(|| self.literal_len().cmp(&other.literal_len()))
+            .then_with(|| other.subtree.cmp(&self.subtree))
+    }
+}
+
+impl fmt::Display for CapturePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.pattern)
+    }
+}
+
+/// Patterns are equal if they are the same after normalization
+impl PartialEq for CapturePattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern == other.pattern
+    }
+}
+
+impl Eq for CapturePattern {}
+
+impl TryFrom<&str> for CapturePattern {
+    type Error = CapturePatternError;
+
+    fn try_from(pattern: &str) -> Result<Self, Self::Error> {
+        Self::new(pattern)
+    }
+}
+
+fn split_segments(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|segment| !segment.is_empty())
+}
+
+fn decode(segment: &str) -> String {
+    percent_encoding::percent_decode_str(segment)
+        .decode_utf8_lossy()
+        .into_owned()
+}
+
+/// Count the literal characters of a glob segment, `*`, `?` and whole `[...]` classes are not literal
+fn glob_literal_len(glob: &str) -> usize {
+    let mut len = 0;
+    let mut chars = glob.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' | '?' => {}
+            '[' => {
+                // a `]` right after `[` or `[!` is part of the class
+                let mut first = true;
+                for c in chars.by_ref() {
+                    if c == ']' && !first {
+                        break;
+                    }
+                    first = first && c == '!';
+                }
+            }
+            '\\' => {
+                if chars.next().is_some() {
+                    len += 1;
+                }
+            }
+            _ => len += 1,
+        }
+    }
+    len
+}
+
+fn has_wildcard(segment: &str) -> bool {
+    segment.contains(['*', '?', '['])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_exact_prefix_and_glob_patterns() {
+        let exact = CapturePattern::new("/api/").unwrap();
+        assert!(exact.is_exact());
+        assert!(exact.matches("/api"));
+        assert!(!exact.matches("/api/v2"));
+
+        let prefix = CapturePattern::new("/api/*").unwrap();
+        assert!(prefix.is_prefix());
+        assert!(prefix.matches("/api"));
+        assert!(prefix.matches("/api/v2/users"));
+        assert!(!prefix.matches("/apiv2"));
+
+        let glob = CapturePattern::new("/api/v?/*s").unwrap();
+        assert!(glob.matches("/api/v2/users"));
+        assert!(!glob.matches("/api/v2/user"));
+        assert!(!glob.matches("/api/v22/users"));
+        assert!(!glob.matches("/api/v2/admin/users"));
+
+        let class = CapturePattern::new("/api/v[12]").unwrap();
+        assert!(class.matches("/api/v1"));
+        assert!(!class.matches("/api/v3"));
+
+        assert!(
+            CapturePattern::new("/*")
+                .unwrap()
+                .matches("/anything/at/all")
+        );
+        assert!(CapturePattern::new("/").unwrap().matches("/"));
+    }
+
+    #[test]
+    fn extracts_named_segments() {
+        let pattern = CapturePattern::new("/tenants/{tenant}/files/{*path}").unwrap();
+        assert!(pattern.has_params());
+        assert!(pattern.is_prefix());
+        assert_eq!(
+            pattern.match_path("/tenants/42/files/docs/a%20b.txt"),
+            Some(vec![
+                ("tenant".to_string(), "42".to_string()),
+                ("path".to_string(), "docs/a b.txt".to_string()),
+            ])
+        );
+        assert_eq!(
+            pattern.match_path("/tenants/42/files"),
+            Some(vec![
+                ("tenant".to_string(), "42".to_string()),
+                ("path".to_string(), String::new()),
+            ])
+        );
+        assert_eq!(pattern.match_path("/tenants/42"), None);
+
+        let pattern = CapturePattern::new("/users/{id}").unwrap();
+        assert!(!pattern.is_prefix());
+        assert_eq!(pattern.match_path("/users/7/posts"), None);
+        assert_eq!(pattern.capture_path().as_deref(), Some("/users"));
+        assert!(pattern.handles_capture_path("/users"));
+        assert!(pattern.handles_capture_path("/"));
+        assert!(!pattern.handles_capture_path("/posts"));
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        assert!(matches!(
+            CapturePattern::new("/files/{*path}/raw"),
+            Err(CapturePatternError::CatchAllNotLast { .. })
+        ));
+        assert!(matches!(
+            CapturePattern::new("/files/[a"),
+            Err(CapturePatternError::InvalidGlob { .. })
+        ));
+    }
+
+    #[test]
+    fn advertises_capture_paths() {
+        assert_eq!(
+            CapturePattern::new("/api/")
+                .unwrap()
+                .capture_path()
+                .as_deref(),
+            Some("/api")
+        );
+        assert_eq!(
+            CapturePattern::new("/api/v2/*")
+                .unwrap()
+                .capture_path()
+                .as_deref(),
+            Some("/api/v2")
+        );
+        assert_eq!(
+            CapturePattern::new("/*").unwrap().capture_path().as_deref(),
+            Some("/")
+        );
+        assert_eq!(CapturePattern::new("/api/v*").unwrap().capture_path(), None);
+    }
+
+    #[test]
+    fn orders_patterns_by_specificity() {
+        let ordered = [
+            "/api/*",
+            "/api/v*",
+            "/api/v2/*",
+            "/api/v2/u*",
+            "/api/v2/users",
+        ];
+        for pair in ordered.windows(2) {
+            assert_eq!(
+                CapturePattern::new(pair[0])
+                    .unwrap()
+                    .cmp_specificity(&CapturePattern::new(pair[1]).unwrap()),
+                Ordering::Less,
+                "{} should be less specific than {}",
+                pair[0],
+                pair[1]
+            );
+        }
+        // a character class is a single wildcard, however many characters it lists
+        assert_eq!(
+            CapturePattern::new("/[abcdefghij]")
+                .unwrap()
+                .cmp_specificity(&CapturePattern::new("/abcdefgh/*").unwrap()),
+            Ordering::Less
+        );
+        assert_eq!(
+            CapturePattern::new("/v[12]x")
+                .unwrap()
+                .cmp_specificity(&CapturePattern::new("/v?x").unwrap()),
+            Ordering::Equal
+        );
+        assert_eq!(glob_literal_len("[]a]b[!]c]d"), 2);
+        assert_eq!(
+            CapturePattern::new("/a*/*")
+                .unwrap()
+                .cmp_specificity(&CapturePattern::new("/*b/*").unwrap()),
+            Ordering::Equal
+        );
+    }
+}
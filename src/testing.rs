@@ -0,0 +1,511 @@
+//! In-process test harness for plugin routers.
+//!
+//! [`PluginTestClient`] sends requests to a plugin's axum `Router` the same way Zoraxy would,
+//! deriving the ingress paths from the plugin's `IntroSpect`.
+//!
+//! ```no_run
+//! # async fn example(app: axum::Router, intro_spect: zoraxy_rs::IntroSpect) {
+//! use zoraxy_rs::testing::{PluginTestClient, SniffRequestBuilder};
+//! use zoraxy_rs::SniffDecision;
+//!
+//! let mut client = PluginTestClient::new(app, intro_spect);
+//! client
+//!     .static_capture("/test_a", "/test_a/x?y=1")
+//!     .await
+//!     .assert_status(200);
+//! client
+//!     .sniff(SniffRequestBuilder::new("/foobar"))
+//!     .await
+//!     .assert_sniff_decision(SniffDecision::Accept);
+//! assert!(client.terminate().await);
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::Router;
+use axum::body::{Body, Bytes};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderMap, Method, Request, StatusCode};
+use serde::de::DeserializeOwned;
+use tokio::sync::broadcast;
+use tower::ServiceExt;
+
+use crate::dynamic_router::REQUEST_ID_HEADER;
+use crate::static_router::{CAPTURE_HEADER, ORIGINAL_URI_HEADER};
+use crate::termination::{Interrupted, Terminator};
+use crate::types::{ControlStatusCode, Event, EventPayload, IntroSpect};
+use crate::{SniffDecision, with_termination_route};
+
+static REQUEST_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+fn next_id(prefix: &str) -> String {
+    format!(
+        "{prefix}-{}",
+        REQUEST_COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Sends requests to a plugin's `Router` the way Zoraxy would
+pub struct PluginTestClient {
+    app: Router,
+    intro_spect: IntroSpect,
+    termination_rx: Option<broadcast::Receiver<Interrupted>>,
+}
+
+impl PluginTestClient {
+    /// Create a new `PluginTestClient` for the plugin's `Router` and `IntroSpect`
+    ///
+    /// If the `IntroSpect` has a UI path, the termination route is mounted the same way
+    /// `start_plugin` does, so `terminate` can be tested.
+    #[must_use]
+    pub fn new(app: Router, intro_spect: IntroSpect) -> Self {
+        let (app, termination_rx) = match intro_spect.ui_path() {
+            Some(ui_path) => {
+                let (tx, rx) = broadcast::channel(1);
+                (
+                    with_termination_route(app, ui_path, Terminator::new(tx)),
+                    Some(rx),
+                )
+            }
+            None => (app, None),
+        };
+
+        Self {
+            app,
+            intro_spect,
+            termination_rx,
+        }
+    }
+
+    #[must_use]
+    pub const fn intro_spect(&self) -> &IntroSpect {
+        &self.intro_spect
+    }
+
+    /// Send an arbitrary request to the plugin
+    ///
+    /// # Panics
+    /// * Panics if the response body can't be read, e.g. a handler's body stream fails
+    pub async fn send(&self, req: Request<Body>) -> TestResponse {
+        let resp = self
+            .app
+            .clone()
+            .oneshot(req)
+            .await
+            .unwrap_or_else(|err| match err {});
+        let (parts, body) = resp.into_parts();
+        let body = axum::body::to_bytes(body, usize::MAX)
+            .await
+            .expect("Failed to read response body");
+        TestResponse {
+            status: parts.status,
+            headers: parts.headers,
+            body,
+        }
+    }
+
+    /// Send a GET request to the static capture ingress, as if Zoraxy matched `capture_path` for `uri`
+    ///
+    /// # Panics
+    /// * Panics if the `IntroSpect` has no static capture settings
+    pub async fn static_capture(&self, capture_path: &str, uri: &str) -> TestResponse {
+        self.static_capture_with(Method::GET, capture_path, uri, Body::empty())
+            .await
+    }
+
+    /// Send a request to the static capture ingress, as if Zoraxy matched `capture_path` for `uri`
+    ///
+    /// # Panics
+    /// * Panics if the `IntroSpect` has no static capture settings
+    pub async fn static_capture_with(
+        &self,
+        method: Method,
+        capture_path: &str,
+        uri: &str,
+        body: Body,
+    ) -> TestResponse {
+        let settings = self
+            .intro_spect
+            .static_capture_settings()
+            .expect("IntroSpect has no static capture settings");
+        let ingress = format!(
+            "{}/",
+            settings.static_capture_ingress().trim_end_matches('/')
+        );
+
+        let req = Request::builder()
+            .method(method)
+            .uri(ingress)
+            .header(CAPTURE_HEADER, capture_path)
+            .header(ORIGINAL_URI_HEADER, uri)
+            .header(REQUEST_ID_HEADER, next_id("static"))
+            .body(body)
+            .expect("Failed to build static capture request");
+        self.send(req).await
+    }
+
+    /// Send a sniff request to the dynamic capture sniff path
+    ///
+    /// # Panics
+    /// * Panics if the `IntroSpect` has no dynamic capture settings
+    pub async fn sniff(&self, sniff: SniffRequestBuilder) -> TestResponse {
+        let settings = self
+            .intro_spect
+            .dynamic_capture_settings()
+            .expect("IntroSpect has no dynamic capture settings");
+        let sniff_path = format!(
+            "{}/",
+            settings.dynamic_capture_sniff().trim_end_matches('/')
+        );
+
+        self.send(sniff.build(&sniff_path)).await
+    }
+
+    /// Send a GET request for `uri` to the dynamic capture ingress, as if the sniff accepted it
+    ///
+    /// # Panics
+    /// * Panics if the `IntroSpect` has no dynamic capture settings
+    pub async fn dynamic_capture(&self, uri: &str, request_id: &str) -> TestResponse {
+        let settings = self
+            .intro_spect
+            .dynamic_capture_settings()
+            .expect("IntroSpect has no dynamic capture settings");
+        let path = format!(
+            "{}/{}",
+            settings.dynamic_capture_ingress().trim_end_matches('/'),
+            uri.trim_start_matches('/')
+        );
+
+        let req = Request::builder()
+            .uri(path)
+            .header(REQUEST_ID_HEADER, request_id)
+            .body(Body::empty())
+            .expect("Failed to build dynamic capture request");
+        self.send(req).await
+    }
+
+    /// POST an event to the subscription path
+    ///
+    /// # Panics
+    /// * Panics if the `IntroSpect` has no subscriptions
+    pub async fn deliver_event(&self, payload: EventPayload) -> TestResponse {
+        let subscriptions = self
+            .intro_spect
+            .subscriptions()
+            .expect("IntroSpect has no subscriptions");
+        let event = Event {
+            name: payload.get_name(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs().try_into().unwrap_or(i64::MAX)),
+            uuid: next_id("event"),
+            data: payload,
+        };
+        let path = format!(
+            "{}/{}",
+            subscriptions.subscription_path().trim_end_matches('/'),
+            event.name
+        );
+
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri(path)
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(
+                serde_json::to_vec(&event).expect("Failed to serialize Event"),
+            ))
+            .expect("Failed to build event request");
+        self.send(req).await
+    }
+
+    /// Call the termination route, returns whether a termination signal was sent
+    ///
+    /// # Panics
+    /// * Panics if the `IntroSpect` has no UI path
+    pub async fn terminate(&mut self) -> bool {
+        let ui_path = self
+            .intro_spect
+            .ui_path()
+            .expect("IntroSpect has no UI path");
+        let term_path = format!("{}/term", ui_path.trim_end_matches('/'));
+
+        let req = Request::builder()
+            .uri(term_path)
+            .body(Body::empty())
+            .expect("Failed to build termination request");
+        let resp = self.send(req).await;
+
+        resp.status.is_success()
+            && self
+                .termination_rx
+                .as_mut()
+                .is_some_and(|rx| rx.try_recv() == Ok(Interrupted::UserInt))
+    }
+}
+
+/// Builds the sniff request Zoraxy sends to the dynamic capture sniff path
+#[derive(Debug, Clone)]
+pub struct SniffRequestBuilder {
+    method: String,
+    hostname: String,
+    request_uri: String,
+    header: HashMap<String, Vec<String>>,
+    remote_addr: String,
+    proto: (i32, i32),
+    request_id: String,
+}
+
+impl SniffRequestBuilder {
+    /// Create a new `SniffRequestBuilder` for a GET request to `request_uri` on `localhost`
+    #[must_use]
+    pub fn new<S: AsRef<str>>(request_uri: S) -> Self {
+        Self {
+            method: "GET".to_string(),
+            hostname: "localhost".to_string(),
+            request_uri: request_uri.as_ref().to_string(),
+            header: HashMap::new(),
+            remote_addr: "127.0.0.1:54321".to_string(),
+            proto: (1, 1),
+            request_id: next_id("sniff"),
+        }
+    }
+
+    /// Set the HTTP method of the sniffed request
+    #[must_use]
+    pub fn method<S: AsRef<str>>(mut self, method: S) -> Self {
+        self.method = method.as_ref().to_string();
+        self
+    }
+
+    /// Set the hostname of the sniffed request
+    #[must_use]
+    pub fn hostname<S: AsRef<str>>(mut self, hostname: S) -> Self {
+        self.hostname = hostname.as_ref().to_string();
+        self
+    }
+
+    /// Add a header value to the sniffed request
+    #[must_use]
+    pub fn header<S: AsRef<str>>(mut self, name: S, value: S) -> Self {
+        self.header
+            .entry(name.as_ref().to_string())
+            .or_default()
+            .push(value.as_ref().to_string());
+        self
+    }
+
+    /// Set the remote address of the sniffed request (e.g. `127.0.0.1:54321`)
+    #[must_use]
+    pub fn remote_addr<S: AsRef<str>>(mut self, remote_addr: S) -> Self {
+        self.remote_addr = remote_addr.as_ref().to_string();
+        self
+    }
+
+    /// Set the HTTP protocol version of the sniffed request, as `(major, minor)`
+    #[must_use]
+    pub const fn proto(mut self, major: i32, minor: i32) -> Self {
+        self.proto = (major, minor);
+        self
+    }
+
+    /// Set the Zoraxy request id of the sniffed request
+    #[must_use]
+    pub fn request_id<S: AsRef<str>>(mut self, request_id: S) -> Self {
+        self.request_id = request_id.as_ref().to_string();
+        self
+    }
+
+    /// Build the sniff request, to be sent to `sniff_path`
+    ///
+    /// # Panics
+    /// * Panics if the request can't be built
+    #[must_use]
+    pub fn build(self, sniff_path: &str) -> Request<Body> {
+        let (proto_major, proto_minor) = self.proto;
+        let payload = serde_json::json!({
+            "method": self.method,
+            "hostname": self.hostname,
+            "url": self.request_uri,
+            "header": self.header,
+            "remote_addr": self.remote_addr,
+            "host": self.hostname,
+            "request_uri": self.request_uri,
+            "proto": format!("HTTP/{proto_major}.{proto_minor}"),
+            "proto_major": proto_major,
+            "proto_minor": proto_minor,
+        });
+
+        Request::builder()
+            .method(Method::POST)
+            .uri(sniff_path)
+            .header(CONTENT_TYPE, "application/json")
+            .header(REQUEST_ID_HEADER, self.request_id)
+            .body(Body::from(payload.to_string()))
+            .expect("Failed to build sniff request")
+    }
+}
+
+/// A response from the plugin, with assertion helpers
+#[derive(Debug, Clone)]
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl TestResponse {
+    /// Get the response body as text
+    ///
+    /// # Panics
+    /// * Panics if the body is not valid UTF-8
+    #[must_use]
+    pub fn text(&self) -> &str {
+        std::str::from_utf8(&self.body).expect("Response body is not valid UTF-8")
+    }
+
+    /// Decode the response body as JSON
+    ///
+    /// # Panics
+    /// * Panics if the body can't be decoded as `T`
+    #[must_use]
+    pub fn json<T: DeserializeOwned>(&self) -> T {
+        serde_json::from_slice(&self.body).expect("Failed to decode response body")
+    }
+
+    /// Assert the response has the given status code
+    ///
+    /// # Panics
+    /// * Panics if the status code doesn't match
+    #[track_caller]
+    pub fn assert_status(&self, status: u16) -> &Self {
+        assert_eq!(
+            self.status.as_u16(),
+            status,
+            "unexpected status, body: {}",
+            String::from_utf8_lossy(&self.body)
+        );
+        self
+    }
+
+    /// Assert the response is the given `SniffDecision`
+    ///
+    /// # Panics
+    /// * Panics if the response is not the expected sniff decision
+    #[track_caller]
+    pub fn assert_sniff_decision(&self, decision: SniffDecision) -> &Self {
+        let actual = match self.status {
+            StatusCode::OK => Some(SniffDecision::Accept),
+            StatusCode::NOT_IMPLEMENTED => Some(SniffDecision::Skip),
+            _ => None,
+        };
+        assert_eq!(
+            actual,
+            Some(decision),
+            "unexpected sniff response {}, body: {}",
+            self.status,
+            String::from_utf8_lossy(&self.body)
+        );
+        self
+    }
+
+    /// Assert the response has the status code of the given `ControlStatusCode`
+    ///
+    /// # Panics
+    /// * Panics if the status code doesn't match
+    #[track_caller]
+    pub fn assert_control_status(&self, code: ControlStatusCode) -> &Self {
+        self.assert_status(code as u16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        BlacklistToggledEvent, DynamicCaptureService, DynamicCaptureSettings,
+        DynamicSniffForwardRequest, EventName, PluginMetadata, PluginType, StaticCaptureSettings,
+        StaticPathRouter, SubscriptionsSettings,
+    };
+    use axum::handler::HandlerWithoutStateExt;
+    use axum::routing::post;
+    use std::sync::Arc;
+
+    fn intro_spect() -> IntroSpect {
+        let metadata = PluginMetadata::new(PluginType::Router).with_id("com.example.test");
+        IntroSpect::new(metadata)
+            .with_ui_path("/ui")
+            .with_static_capture_settings(
+                StaticCaptureSettings::new("/s_capture").add_static_capture_path("/test_a"),
+            )
+            .with_dynamic_capture_settings(DynamicCaptureSettings::new("/d_sniff", "/d_capture"))
+            .with_subscriptions(
+                SubscriptionsSettings::new("/notifyme")
+                    .add_event_subscription(EventName::BlacklistToggled, "test"),
+            )
+    }
+
+    fn app() -> Router {
+        async fn handler_a(req: Request<Body>) -> String {
+            req.uri().to_string()
+        }
+        async fn sniff(sniff: DynamicSniffForwardRequest) -> SniffDecision {
+            if sniff.request_uri.starts_with("/foobar") {
+                SniffDecision::Accept
+            } else {
+                SniffDecision::Skip
+            }
+        }
+        async fn capture(req: Request<Body>) -> String {
+            req.uri().path().to_string()
+        }
+        async fn event(axum::Json(event): axum::Json<Event>) -> String {
+            event.name.to_string()
+        }
+
+        let path_router = StaticPathRouter::default();
+        path_router.register_path_service("/test_a", handler_a.into_service());
+
+        Router::new()
+            .route_service("/s_capture/", Arc::new(path_router).into_capture_service())
+            .route("/d_sniff/", post(sniff))
+            .nest_service(
+                "/d_capture/",
+                DynamicCaptureService::new("/d_capture/", capture.into_service()),
+            )
+            .route("/notifyme/{event_name}", post(event))
+    }
+
+    #[tokio::test]
+    async fn drives_plugin_router() {
+        let mut client = PluginTestClient::new(app(), intro_spect());
+
+        let resp = client.static_capture("/test_a", "/test_a/x?y=1").await;
+        resp.assert_status(200);
+        assert_eq!(resp.text(), "/test_a/x?y=1");
+
+        client
+            .sniff(SniffRequestBuilder::new("/foobar/baz"))
+            .await
+            .assert_sniff_decision(SniffDecision::Accept);
+        client
+            .sniff(SniffRequestBuilder::new("/other").method("POST"))
+            .await
+            .assert_sniff_decision(SniffDecision::Skip);
+
+        let resp = client.dynamic_capture("/foobar/baz", "abc").await;
+        assert_eq!(resp.text(), "/foobar/baz");
+
+        let resp = client
+            .deliver_event(EventPayload::BlacklistToggled(BlacklistToggledEvent {
+                rule_id: "default".to_string(),
+                enabled: true,
+            }))
+            .await;
+        assert_eq!(resp.text(), "blacklistToggled");
+
+        assert!(client.terminate().await);
+    }
+}
@@ -4,7 +4,7 @@
 /// the plugin shell read this payload as JSON and configure itself
 /// by the supplied values like starting a web server at given port
 /// that listens to 127.0.0.1:port
-#[derive(serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct ConfigureSpec {
     /// Port to listen
     pub port: u16,
@@ -12,12 +12,14 @@ pub struct ConfigureSpec {
     #[serde(rename = "runtime_const")]
     pub runtime_constants: RuntimeConstants,
     /// API key for accessing Zoraxy APIs, if the plugin has permitted endpoints
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub api_key: Option<String>,
     /// The port that Zoraxy is running on, used for making API calls to Zoraxy
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub zoraxy_port: Option<u16>,
 }
 
-#[derive(serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct RuntimeConstants {
     /// Zoraxy Version
     pub zoraxy_version: String,
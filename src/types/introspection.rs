@@ -6,7 +6,7 @@ use crate::EventName;
 ///
 /// When the plugin is initialized with -introspect flag,
 /// the plugin shell returns this payload as JSON and exits.
-#[derive(serde::Serialize, Debug, Clone)]
+#[derive(serde::Serialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub struct IntroSpect {
     /// Plugin metadata
@@ -91,9 +91,328 @@ impl IntroSpect {
         self.subscriptions = Some(subscriptions);
         self
     }
+
+    /// Get the plugin metadata of the `IntroSpect`
+    #[must_use]
+    pub const fn metadata(&self) -> &PluginMetadata {
+        &self.metadata
+    }
+
+    /// Get the static capture settings of the `IntroSpect`, if any
+    #[must_use]
+    pub const fn static_capture_settings(&self) -> Option<&StaticCaptureSettings> {
+        self.static_capture_settings.as_ref()
+    }
+
+    /// Get the dynamic capture settings of the `IntroSpect`, if any
+    #[must_use]
+    pub const fn dynamic_capture_settings(&self) -> Option<&DynamicCaptureSettings> {
+        self.dynamic_capture_settings.as_ref()
+    }
+
+    /// Get the UI path of the `IntroSpect`, if any
+    #[must_use]
+    pub fn ui_path(&self) -> Option<&str> {
+        self.ui_path.as_deref()
+    }
+
+    /// Get the subscriptions settings of the `IntroSpect`, if any
+    #[must_use]
+    pub const fn subscriptions(&self) -> Option<&SubscriptionsSettings> {
+        self.subscriptions.as_ref()
+    }
+
+    /// Get the permitted API endpoints of the `IntroSpect`
+    #[must_use]
+    pub fn permitted_api_endpoints(&self) -> &[PermittedApiEndpoint] {
+        &self.permitted_api_endpoints
+    }
+
+    /// Check the `IntroSpect` for problems that would make Zoraxy reject the plugin
+    ///
+    /// # Errors
+    /// * Returns an `IntroSpectValidationError` listing every problem found
+    pub fn validate(&self) -> Result<(), IntroSpectValidationError> {
+        let mut problems = Vec::new();
+
+        if !is_reverse_dns(&self.metadata.id) {
+            problems.push(IntroSpectProblem::InvalidId(self.metadata.id.clone()));
+        }
+
+        let is_router = self.metadata.plugin_type == PluginType::Router;
+        if !is_router && self.static_capture_settings.is_some() {
+            problems.push(IntroSpectProblem::CaptureSettingsOnNonRouter("static"));
+        }
+        if !is_router && self.dynamic_capture_settings.is_some() {
+            problems.push(IntroSpectProblem::CaptureSettingsOnNonRouter("dynamic"));
+        }
+        if is_router
+            && self.static_capture_settings.is_none()
+            && self.dynamic_capture_settings.is_none()
+        {
+            problems.push(IntroSpectProblem::RouterWithoutCaptureSettings);
+        }
+
+        // paths served by the plugin itself, these must not overlap each other
+        let mut ingress_paths: Vec<(&'static str, &str)> = Vec::new();
+        // Zoraxy only forwards its plugin UI prefix to the UI path, so a UI served at the root
+        // shares the plugin's paths without capturing any of them
+        if let Some(ui_path) = &self.ui_path
+            && ui_path != "/"
+        {
+            ingress_paths.push(("ui_path", ui_path));
+        }
+        if let Some(settings) = &self.static_capture_settings {
+            ingress_paths.push(("static_capture_ingress", &settings.static_capture_ingress));
+
+            let mut seen = Vec::new();
+            for rule in &settings.static_capture_paths {
+                let path = rule.capture_path.as_str();
+                if !path.starts_with('/') {
+                    problems.push(IntroSpectProblem::RelativePath {
+                        field: "static_capture_paths",
+                        path: path.to_string(),
+                    });
+                }
+                if seen.contains(&path) {
+                    problems.push(IntroSpectProblem::DuplicateCapturePath(path.to_string()));
+                } else {
+                    seen.push(path);
+                }
+            }
+        }
+        if let Some(settings) = &self.dynamic_capture_settings {
+            ingress_paths.push(("dynamic_capture_sniff", &settings.dynamic_capture_sniff));
+            ingress_paths.push(("dynamic_capture_ingress", &settings.dynamic_capture_ingress));
+        }
+        if let Some(subscriptions) = &self.subscriptions {
+            ingress_paths.push(("subscription_path", &subscriptions.subscription_path));
+        }
+
+        for (field, path) in &ingress_paths {
+            if !path.starts_with('/') {
+                problems.push(IntroSpectProblem::RelativePath {
+                    field,
+                    path: (*path).to_string(),
+                });
+            }
+        }
+        for (i, (first, first_path)) in ingress_paths.iter().enumerate() {
+            for (second, second_path) in &ingress_paths[i + 1..] {
+                if paths_overlap(first_path, second_path) {
+                    problems.push(IntroSpectProblem::CollidingPaths {
+                        first,
+                        first_path: (*first_path).to_string(),
+                        second,
+                        second_path: (*second_path).to_string(),
+                    });
+                }
+            }
+        }
+
+        for endpoint in &self.permitted_api_endpoints {
+            if !HTTP_METHODS.contains(&endpoint.method.as_str()) {
+                problems.push(IntroSpectProblem::InvalidApiMethod {
+                    method: endpoint.method.clone(),
+                    endpoint: endpoint.endpoint.clone(),
+                });
+            }
+            if !endpoint.endpoint.starts_with("/plugin/api/") {
+                problems.push(IntroSpectProblem::InvalidApiEndpoint(
+                    endpoint.endpoint.clone(),
+                ));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(IntroSpectValidationError { problems })
+        }
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for IntroSpect {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        // The wire format is flat, and plugins written against the Go library emit every field,
+        // using empty strings and nulls for the settings they don't use.
+        #[derive(serde::Deserialize)]
+        struct IntroSpectFields {
+            #[serde(flatten)]
+            metadata: PluginMetadata,
+            #[serde(default, deserialize_with = "null_as_default")]
+            static_capture_paths: Vec<StaticCaptureRule>,
+            #[serde(default, deserialize_with = "null_as_default")]
+            static_capture_ingress: String,
+            #[serde(default, deserialize_with = "null_as_default")]
+            dynamic_capture_sniff: String,
+            #[serde(default, deserialize_with = "null_as_default")]
+            dynamic_capture_ingress: String,
+            #[serde(default, deserialize_with = "null_as_default")]
+            ui_path: String,
+            #[serde(default, deserialize_with = "null_as_default")]
+            subscription_path: String,
+            #[serde(default, deserialize_with = "known_event_subscriptions")]
+            subscriptions_events: HashMap<EventName, String>,
+            #[serde(default, deserialize_with = "null_as_default")]
+            permitted_api_endpoints: Vec<PermittedApiEndpoint>,
+        }
+
+        let fields = IntroSpectFields::deserialize(deserializer)?;
+
+        let static_capture_settings = (!fields.static_capture_ingress.is_empty()
+            || !fields.static_capture_paths.is_empty())
+        .then_some(StaticCaptureSettings {
+            static_capture_paths: fields.static_capture_paths,
+            static_capture_ingress: fields.static_capture_ingress,
+        });
+        let dynamic_capture_settings = (!fields.dynamic_capture_sniff.is_empty()
+            || !fields.dynamic_capture_ingress.is_empty())
+        .then_some(DynamicCaptureSettings {
+            dynamic_capture_sniff: fields.dynamic_capture_sniff,
+            dynamic_capture_ingress: fields.dynamic_capture_ingress,
+        });
+        let subscriptions = (!fields.subscription_path.is_empty()
+            || !fields.subscriptions_events.is_empty())
+        .then_some(SubscriptionsSettings {
+            subscription_path: fields.subscription_path,
+            event_subscriptions: fields.subscriptions_events,
+        });
+
+        Ok(Self {
+            metadata: fields.metadata,
+            static_capture_settings,
+            dynamic_capture_settings,
+            ui_path: (!fields.ui_path.is_empty()).then_some(fields.ui_path),
+            subscriptions,
+            permitted_api_endpoints: fields.permitted_api_endpoints,
+        })
+    }
+}
+
+/// Deserialize `null` as the default value, Go marshals empty slices and maps as `null`
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::Deserialize<'de> + Default,
+{
+    Ok(<Option<T> as serde::Deserialize>::deserialize(deserializer)?.unwrap_or_default())
 }
 
-#[derive(serde::Serialize, Debug, Clone)]
+/// Deserialize event subscriptions, skipping events this crate doesn't know with a warning,
+/// so that introspection output of plugins targeting newer Zoraxy versions still parses
+fn known_event_subscriptions<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<EventName, String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::IntoDeserializer;
+
+    let subscriptions: HashMap<String, String> = null_as_default(deserializer)?;
+    Ok(subscriptions
+        .into_iter()
+        .filter_map(|(event, comment)| {
+            let name: Result<EventName, serde::de::value::Error> =
+                serde::Deserialize::deserialize(event.as_str().into_deserializer());
+            name.map(|name| (name, comment))
+                .inspect_err(|_| tracing::warn!(event, "Skipping subscription to unknown event"))
+                .ok()
+        })
+        .collect())
+}
+
+const HTTP_METHODS: [&str; 9] = [
+    "GET", "HEAD", "POST", "PUT", "DELETE", "CONNECT", "OPTIONS", "TRACE", "PATCH",
+];
+
+/// Check that `id` is in reverse domain name notation, e.g. `com.example.plugin`
+fn is_reverse_dns(id: &str) -> bool {
+    let labels: Vec<&str> = id.split('.').collect();
+    labels.len() >= 2
+        && labels.iter().all(|label| {
+            label.starts_with(|c: char| c.is_ascii_alphabetic())
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+/// Check if one path is the same as, or a subpath of, the other
+fn paths_overlap(a: &str, b: &str) -> bool {
+    let a = a.trim_end_matches('/');
+    let b = b.trim_end_matches('/');
+    let (shorter, longer) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    shorter.is_empty()
+        || longer == shorter
+        || longer
+            .strip_prefix(shorter)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// A problem found by `IntroSpect::validate`
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum IntroSpectProblem {
+    /// The plugin ID is not in reverse domain name notation
+    #[error("plugin id {0:?} is not in reverse domain name notation (e.g. com.example.plugin)")]
+    InvalidId(String),
+    /// A path is not absolute
+    #[error("{field} {path:?} is not an absolute path")]
+    RelativePath { field: &'static str, path: String },
+    /// Two paths served by the plugin overlap
+    #[error("{first} {first_path:?} collides with {second} {second_path:?}")]
+    CollidingPaths {
+        first: &'static str,
+        first_path: String,
+        second: &'static str,
+        second_path: String,
+    },
+    /// A static capture path is declared more than once
+    #[error("static capture path {0:?} is declared more than once")]
+    DuplicateCapturePath(String),
+    /// Capture settings are declared on a plugin that isn't a `PluginType::Router`
+    #[error("{0} capture settings are only supported by router plugins")]
+    CaptureSettingsOnNonRouter(&'static str),
+    /// A `PluginType::Router` plugin declares no capture settings
+    #[error("router plugins must declare static or dynamic capture settings")]
+    RouterWithoutCaptureSettings,
+    /// A permitted API endpoint has an invalid HTTP method
+    #[error("permitted API endpoint {endpoint:?} has invalid HTTP method {method:?}")]
+    InvalidApiMethod { method: String, endpoint: String },
+    /// A permitted API endpoint is not under `/plugin/api`
+    #[error("permitted API endpoint {0:?} is not under /plugin/api/")]
+    InvalidApiEndpoint(String),
+}
+
+/// Error returned by `IntroSpect::validate`, lists every problem found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntroSpectValidationError {
+    problems: Vec<IntroSpectProblem>,
+}
+
+impl IntroSpectValidationError {
+    #[must_use]
+    pub fn problems(&self) -> &[IntroSpectProblem] {
+        &self.problems
+    }
+}
+
+impl std::fmt::Display for IntroSpectValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid introspect:")?;
+        for problem in &self.problems {
+            write!(f, "\n  - {problem}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for IntroSpectValidationError {}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct PluginMetadata {
     /// Unique ID of your plugin
     /// recommended to use reverse domain name notation
@@ -104,7 +423,7 @@ pub struct PluginMetadata {
     /// Author name of your plugin
     author: String,
     /// Author contact information, like email
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     contact: String,
     /// Description of your plugin
     description: String,
@@ -190,6 +509,54 @@ impl PluginMetadata {
         self.url = url.as_ref().to_string();
         self
     }
+
+    /// Get the ID of the plugin
+    #[must_use]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Get the name of the plugin
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the author of the plugin
+    #[must_use]
+    pub fn author(&self) -> &str {
+        &self.author
+    }
+
+    /// Get the contact of the plugin author
+    #[must_use]
+    pub fn contact(&self) -> &str {
+        &self.contact
+    }
+
+    /// Get the description of the plugin
+    #[must_use]
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Get the URL of the plugin
+    #[must_use]
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Get the type of the plugin
+    #[must_use]
+    pub const fn plugin_type(&self) -> PluginType {
+        self.plugin_type
+    }
+
+    /// Get the version of the plugin as a `(major, minor, patch)` tuple
+    #[must_use]
+    pub const fn version(&self) -> (u8, u8, u8) {
+        (self.version_major, self.version_minor, self.version_patch)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Copy)]
@@ -208,10 +575,26 @@ impl serde::Serialize for PluginType {
     }
 }
 
+impl<'de> serde::Deserialize<'de> for PluginType {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        match u8::deserialize(deserializer)? {
+            0 => Ok(Self::Router),
+            1 => Ok(Self::Utilities),
+            other => Err(serde::de::Error::invalid_value(
+                serde::de::Unexpected::Unsigned(u64::from(other)),
+                &"0 (Router) or 1 (Utilities)",
+            )),
+        }
+    }
+}
+
 /// Static Capture Settings
 ///
 /// Once plugin is enabled these rules always apply to the enabled HTTP Proxy rule
-#[derive(serde::Serialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub struct StaticCaptureSettings {
     /// Static capture paths of your plugin, see Zoraxy documentation for more details
@@ -236,10 +619,22 @@ impl StaticCaptureSettings {
         self.static_capture_paths.push(StaticCaptureRule::new(rule));
         self
     }
+
+    /// Get the static capture paths of the `StaticCaptureSettings`
+    #[must_use]
+    pub fn static_capture_paths(&self) -> &[StaticCaptureRule] {
+        &self.static_capture_paths
+    }
+
+    /// Get the static capture ingress path of the `StaticCaptureSettings`
+    #[must_use]
+    pub fn static_capture_ingress(&self) -> &str {
+        &self.static_capture_ingress
+    }
 }
 
 /// Static Capture Rule
-#[derive(serde::Serialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub struct StaticCaptureRule {
     capture_path: String,
@@ -253,6 +648,12 @@ impl StaticCaptureRule {
             capture_path: capture_path.as_ref().to_string(),
         }
     }
+
+    /// Get the capture path of the `StaticCaptureRule`
+    #[must_use]
+    pub fn capture_path(&self) -> &str {
+        &self.capture_path
+    }
 }
 
 /// Dynamic Capture Settings
@@ -261,7 +662,7 @@ impl StaticCaptureRule {
 /// if the plugin sniff returns 280, the traffic will be captured
 /// otherwise, the traffic will be forwarded to the next plugin
 /// This is slower than static capture, but more flexible
-#[derive(serde::Serialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub struct DynamicCaptureSettings {
     /// Dynamic capture sniff path of your plugin (e.g. `/d_sniff`)
@@ -279,12 +680,24 @@ impl DynamicCaptureSettings {
             dynamic_capture_ingress: dynamic_capture_ingress.as_ref().to_string(),
         }
     }
+
+    /// Get the dynamic capture sniff path of the `DynamicCaptureSettings`
+    #[must_use]
+    pub fn dynamic_capture_sniff(&self) -> &str {
+        &self.dynamic_capture_sniff
+    }
+
+    /// Get the dynamic capture ingress path of the `DynamicCaptureSettings`
+    #[must_use]
+    pub fn dynamic_capture_ingress(&self) -> &str {
+        &self.dynamic_capture_ingress
+    }
 }
 
 /// Subscriptions Settings
 ///
 /// Once plugin is enabled, Zoraxy will send subscription events to the plugin
-#[derive(serde::Serialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub struct SubscriptionsSettings {
     /// Subscription event path of your plugin (e.g. `/notifyme`),
@@ -292,7 +705,10 @@ pub struct SubscriptionsSettings {
     subscription_path: String,
     /// Event subscriptions of your plugin,
     /// paired with comments describing how the event is used, see Zoraxy documentation for more details
-    #[serde(rename = "subscriptions_events")]
+    #[serde(
+        rename = "subscriptions_events",
+        deserialize_with = "known_event_subscriptions"
+    )]
     event_subscriptions: HashMap<EventName, String>,
 }
 
@@ -317,11 +733,23 @@ impl SubscriptionsSettings {
             .insert(event, description.as_ref().to_string());
         self
     }
+
+    /// Get the subscription event path of the `SubscriptionsSettings`
+    #[must_use]
+    pub fn subscription_path(&self) -> &str {
+        &self.subscription_path
+    }
+
+    /// Get the subscribed events of the `SubscriptionsSettings`, with their comments
+    #[must_use]
+    pub const fn event_subscriptions(&self) -> &HashMap<EventName, String> {
+        &self.event_subscriptions
+    }
 }
 /// Permitted API Endpoint
 ///
 /// An API endpoint that the plugin is allowed to access
-#[derive(serde::Serialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub struct PermittedApiEndpoint {
     /// HTTP method for the API endpoint (e.g., GET, POST)
@@ -329,6 +757,7 @@ pub struct PermittedApiEndpoint {
     ///The API endpoint that the plugin can access
     endpoint: String,
     ///The reason why the plugin needs to access this endpoint
+    #[serde(default)]
     reason: Option<String>,
 }
 
@@ -349,4 +778,199 @@ impl PermittedApiEndpoint {
         self.reason = Some(reason.as_ref().to_string());
         self
     }
+
+    /// Get the HTTP method of the `PermittedApiEndpoint`
+    #[must_use]
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    /// Get the path of the `PermittedApiEndpoint`
+    #[must_use]
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Get the reason the plugin needs the `PermittedApiEndpoint`, if any
+    #[must_use]
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn metadata(plugin_type: PluginType) -> PluginMetadata {
+        PluginMetadata::new(plugin_type)
+            .with_id("com.example.plugin")
+            .with_name("Example")
+            .with_version((1, 0, 0))
+    }
+
+    #[test]
+    fn valid_introspect_passes() {
+        let intro_spect = IntroSpect::new(metadata(PluginType::Router))
+            .with_ui_path("/ui")
+            .with_static_capture_settings(
+                StaticCaptureSettings::new("/s_capture").add_static_capture_path("/test_a"),
+            )
+            .add_permitted_api_endpoint(PermittedApiEndpoint::new(
+                "GET",
+                "/plugin/api/access/list",
+            ));
+
+        assert_eq!(intro_spect.validate(), Ok(()));
+    }
+
+    #[test]
+    fn root_ui_path_does_not_collide() {
+        let intro_spect = IntroSpect::new(metadata(PluginType::Router))
+            .with_ui_path("/")
+            .with_dynamic_capture_settings(DynamicCaptureSettings::new("/d_sniff", "/d_capture"))
+            .with_subscriptions(SubscriptionsSettings::new("/notifyme"));
+
+        assert_eq!(intro_spect.validate(), Ok(()));
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let intro_spect = IntroSpect::new(metadata(PluginType::Utilities).with_id(""))
+            .with_ui_path("/s_capture/ui")
+            .with_static_capture_settings(
+                StaticCaptureSettings::new("/s_capture")
+                    .add_static_capture_path("test_a")
+                    .add_static_capture_path("test_a"),
+            )
+            .add_permitted_api_endpoint(PermittedApiEndpoint::new("FETCH", "/api/proxy/list"));
+
+        let err = intro_spect.validate().unwrap_err();
+        assert_eq!(
+            err.problems(),
+            &[
+                IntroSpectProblem::InvalidId(String::new()),
+                IntroSpectProblem::CaptureSettingsOnNonRouter("static"),
+                IntroSpectProblem::RelativePath {
+                    field: "static_capture_paths",
+                    path: "test_a".to_string()
+                },
+                IntroSpectProblem::RelativePath {
+                    field: "static_capture_paths",
+                    path: "test_a".to_string()
+                },
+                IntroSpectProblem::DuplicateCapturePath("test_a".to_string()),
+                IntroSpectProblem::CollidingPaths {
+                    first: "ui_path",
+                    first_path: "/s_capture/ui".to_string(),
+                    second: "static_capture_ingress",
+                    second_path: "/s_capture".to_string()
+                },
+                IntroSpectProblem::InvalidApiMethod {
+                    method: "FETCH".to_string(),
+                    endpoint: "/api/proxy/list".to_string()
+                },
+                IntroSpectProblem::InvalidApiEndpoint("/api/proxy/list".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let intro_spect = IntroSpect::new(metadata(PluginType::Router).with_contact("a@b.c"))
+            .with_ui_path("/ui")
+            .with_dynamic_capture_settings(DynamicCaptureSettings::new("/d_sniff", "/d_capture"))
+            .with_subscriptions(
+                SubscriptionsSettings::new("/notifyme")
+                    .add_event_subscription(EventName::AccessRuleCreated, "log new rules"),
+            )
+            .add_permitted_api_endpoint(
+                PermittedApiEndpoint::new("GET", "/plugin/api/access/list").with_reason("because"),
+            );
+
+        let json = serde_json::to_string(&intro_spect).unwrap();
+        let parsed: IntroSpect = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(parsed, intro_spect);
+        assert_eq!(parsed.metadata().version(), (1, 0, 0));
+        assert_eq!(
+            parsed
+                .dynamic_capture_settings()
+                .map(DynamicCaptureSettings::dynamic_capture_sniff),
+            Some("/d_sniff")
+        );
+        assert_eq!(
+            parsed.permitted_api_endpoints()[0].reason(),
+            Some("because")
+        );
+    }
+
+    #[test]
+    fn parses_go_plugin_output() {
+        let json = r#"{
+            "id": "com.example.goplugin",
+            "name": "Go Plugin",
+            "author": "foobar",
+            "contact": "",
+            "description": "A plugin built with the Go library",
+            "url": "https://example.com",
+            "type": 0,
+            "version_major": 1,
+            "version_minor": 2,
+            "version_patch": 3,
+            "static_capture_paths": [{"capture_path": "/test_a"}],
+            "static_capture_ingress": "/s_capture",
+            "dynamic_capture_sniff": "",
+            "dynamic_capture_ingress": "",
+            "ui_path": "/ui",
+            "subscription_path": "",
+            "subscriptions_events": null,
+            "permitted_api_endpoints": null
+        }"#;
+
+        let parsed: IntroSpect = serde_json::from_str(json).unwrap();
+
+        assert_eq!(parsed.metadata().plugin_type(), PluginType::Router);
+        assert_eq!(parsed.metadata().contact(), "");
+        assert_eq!(parsed.ui_path(), Some("/ui"));
+        assert_eq!(
+            parsed.static_capture_settings(),
+            Some(&StaticCaptureSettings::new("/s_capture").add_static_capture_path("/test_a"))
+        );
+        assert_eq!(parsed.dynamic_capture_settings(), None);
+        assert_eq!(parsed.subscriptions(), None);
+        assert!(parsed.permitted_api_endpoints().is_empty());
+    }
+
+    #[test]
+    fn skips_unknown_event_subscriptions() {
+        let json = r#"{
+            "subscription_path": "/notifyme",
+            "subscriptions_events": {
+                "accessRuleCreated": "log new rules",
+                "someFutureEvent": "not known yet"
+            }
+        }"#;
+
+        let parsed: SubscriptionsSettings = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            parsed,
+            SubscriptionsSettings::new("/notifyme")
+                .add_event_subscription(EventName::AccessRuleCreated, "log new rules")
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_plugin_type() {
+        assert!(serde_json::from_str::<PluginType>("2").is_err());
+    }
+
+    #[test]
+    fn paths_overlap_on_segment_boundaries() {
+        assert!(paths_overlap("/ui", "/ui/"));
+        assert!(paths_overlap("/ui", "/ui/capture"));
+        assert!(!paths_overlap("/ui", "/ui_capture"));
+        assert!(paths_overlap("/", "/anything"));
+    }
 }
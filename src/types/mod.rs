@@ -1,12 +1,14 @@
 mod configuration;
 mod events;
 mod introspection;
+mod version;
 
 pub use configuration::*;
 pub use events::*;
 pub use introspection::*;
+pub use version::*;
 
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u16)]
 pub enum ControlStatusCode {
     /// Traffic captured by plugin, ask Zoraxy not to process the traffic
@@ -16,3 +18,19 @@ pub enum ControlStatusCode {
     /// Error occurred while processing the traffic, ask Zoraxy to process the traffic and log the error
     Error = 580,
 }
+
+impl ControlStatusCode {
+    /// The HTTP status code Zoraxy expects for this control status
+    #[must_use]
+    pub fn status_code(self) -> axum::http::StatusCode {
+        // every variant is in the valid 100..=999 range, the fallback is never used
+        axum::http::StatusCode::from_u16(self as u16)
+            .unwrap_or(axum::http::StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+impl axum::response::IntoResponse for ControlStatusCode {
+    fn into_response(self) -> axum::response::Response {
+        self.status_code().into_response()
+    }
+}
@@ -0,0 +1,234 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::RuntimeConstants;
+
+/// A Zoraxy release version, parsed from `RuntimeConstants::zoraxy_version`
+///
+/// Versions are ordered semantically, so `3.2.10` is newer than `3.2.9`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ZoraxyVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl ZoraxyVersion {
+    #[must_use]
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+impl fmt::Display for ZoraxyVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl FromStr for ZoraxyVersion {
+    type Err = ZoraxyVersionError;
+
+    /// Parse a version such as `3.2.9`, `v3.2.9` or `3.2.9-dev`,
+    /// a missing patch component is read as `0` and pre-release or build suffixes are ignored
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ZoraxyVersionError::Invalid {
+            version: s.to_string(),
+        };
+
+        let trimmed = s.trim();
+        let trimmed = trimmed.strip_prefix(['v', 'V']).unwrap_or(trimmed);
+        let core = trimmed.split(['-', '+']).next().unwrap_or_default();
+
+        let mut components = core.split('.').map(str::parse::<u32>);
+        let major = components
+            .next()
+            .ok_or_else(invalid)?
+            .map_err(|_| invalid())?;
+        let minor = components
+            .next()
+            .ok_or_else(invalid)?
+            .map_err(|_| invalid())?;
+        let patch = components.next().transpose().map_err(|_| invalid())?;
+        if components.next().is_some() {
+            return Err(invalid());
+        }
+
+        Ok(Self::new(major, minor, patch.unwrap_or(0)))
+    }
+}
+
+/// A plugin feature that is only available on some Zoraxy versions, see [`RuntimeConstants::supports`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Capability {
+    /// Capturing requests by path, see `StaticCaptureSettings`, since Zoraxy 3.2.0
+    StaticCapture,
+    /// Capturing requests by sniffing them, see `DynamicCaptureSettings`, since Zoraxy 3.2.0
+    DynamicCapture,
+    /// Serving a plugin UI through the Zoraxy web UI, since Zoraxy 3.2.0
+    PluginUi,
+    /// Receiving events, see `SubscriptionsSettings`, since Zoraxy 3.2.5
+    EventSubscriptions,
+    /// Calling the Zoraxy API, see `PermittedApiEndpoint`, since Zoraxy 3.2.6
+    PermittedApiEndpoints,
+}
+
+impl Capability {
+    /// The first Zoraxy version supporting this capability
+    #[must_use]
+    pub const fn since(self) -> ZoraxyVersion {
+        match self {
+            Self::StaticCapture | Self::DynamicCapture | Self::PluginUi => {
+                ZoraxyVersion::new(3, 2, 0)
+            }
+            Self::EventSubscriptions => ZoraxyVersion::new(3, 2, 5),
+            Self::PermittedApiEndpoints => ZoraxyVersion::new(3, 2, 6),
+        }
+    }
+}
+
+/// Errors returned when parsing or checking a Zoraxy version
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ZoraxyVersionError {
+    /// The version is not of the form `major.minor[.patch]`
+    #[error("invalid Zoraxy version {version:?}")]
+    Invalid { version: String },
+    /// The running Zoraxy is older than the version the plugin requires
+    #[error("Zoraxy {found} is not supported, this plugin requires Zoraxy {required} or newer")]
+    Unsupported {
+        found: ZoraxyVersion,
+        required: ZoraxyVersion,
+    },
+}
+
+impl RuntimeConstants {
+    /// The version of the Zoraxy instance running the plugin
+    ///
+    /// # Errors
+    /// * Returns an error if `zoraxy_version` is not a valid version
+    pub fn version(&self) -> Result<ZoraxyVersion, ZoraxyVersionError> {
+        self.zoraxy_version.parse()
+    }
+
+    /// Whether the Zoraxy instance running the plugin is at least version `required`,
+    /// e.g. the release that introduced a feature the plugin wants to use
+    ///
+    /// Development builds with an unparseable version are assumed to be new enough.
+    #[must_use]
+    pub fn is_at_least(&self, required: ZoraxyVersion) -> bool {
+        self.version()
+            .map_or(self.development_build, |version| version >= required)
+    }
+
+    /// Whether the Zoraxy instance running the plugin supports `capability`,
+    /// i.e. is at least version [`Capability::since`]
+    ///
+    /// Development builds with an unparseable version are assumed to support everything.
+    #[must_use]
+    pub fn supports(&self, capability: Capability) -> bool {
+        self.is_at_least(capability.since())
+    }
+
+    /// Check that the Zoraxy instance running the plugin is at least version `required`
+    ///
+    /// Development builds only get a warning logged, as their version often lags behind the features they have.
+    ///
+    /// # Errors
+    /// * Returns an error if `zoraxy_version` is invalid or older than `required` on a release build
+    pub fn require_version(&self, required: ZoraxyVersion) -> Result<(), ZoraxyVersionError> {
+        let result = self.version().and_then(|found| {
+            if found >= required {
+                Ok(())
+            } else {
+                Err(ZoraxyVersionError::Unsupported { found, required })
+            }
+        });
+
+        match result {
+            Err(err) if self.development_build => {
+                tracing::warn!("{err}, continuing since this is a development build of Zoraxy");
+                Ok(())
+            }
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn runtime(version: &str, development_build: bool) -> RuntimeConstants {
+        RuntimeConstants {
+            zoraxy_version: version.to_string(),
+            zoraxy_uuid: "x".to_string(),
+            development_build,
+        }
+    }
+
+    #[test]
+    fn parses_versions() {
+        assert_eq!("3.2.9".parse(), Ok(ZoraxyVersion::new(3, 2, 9)));
+        assert_eq!("v3.2.10".parse(), Ok(ZoraxyVersion::new(3, 2, 10)));
+        assert_eq!("3.2".parse(), Ok(ZoraxyVersion::new(3, 2, 0)));
+        assert_eq!("3.2.9-dev+abc".parse(), Ok(ZoraxyVersion::new(3, 2, 9)));
+
+        for invalid in ["", "3", "3.x.1", "3.2.9.1", "dev"] {
+            assert!(
+                invalid.parse::<ZoraxyVersion>().is_err(),
+                "{invalid:?} should be invalid"
+            );
+        }
+    }
+
+    #[test]
+    fn orders_versions_semantically() {
+        assert!(ZoraxyVersion::new(3, 2, 10) > ZoraxyVersion::new(3, 2, 9));
+        assert!(ZoraxyVersion::new(4, 0, 0) > ZoraxyVersion::new(3, 10, 10));
+    }
+
+    #[test]
+    fn compares_running_version() {
+        let required = ZoraxyVersion::new(3, 2, 5);
+        assert!(runtime("3.2.9", false).is_at_least(required));
+        assert!(runtime("3.2.5", false).is_at_least(required));
+        assert!(!runtime("3.2.0", false).is_at_least(required));
+        assert!(!runtime("unknown", false).is_at_least(required));
+        assert!(runtime("unknown", true).is_at_least(required));
+    }
+
+    #[test]
+    fn supports_capabilities_by_version() {
+        assert!(runtime("3.2.9", false).supports(Capability::EventSubscriptions));
+        assert!(runtime("3.2.0", false).supports(Capability::DynamicCapture));
+        assert!(!runtime("3.2.0", false).supports(Capability::EventSubscriptions));
+        assert!(!runtime("3.2.5", false).supports(Capability::PermittedApiEndpoints));
+        assert!(!runtime("3.1.9", false).supports(Capability::StaticCapture));
+        assert!(!runtime("unknown", false).supports(Capability::StaticCapture));
+        assert!(runtime("unknown", true).supports(Capability::PermittedApiEndpoints));
+    }
+
+    #[test]
+    fn requires_version_on_release_builds() {
+        let required = ZoraxyVersion::new(3, 2, 9);
+
+        assert_eq!(runtime("3.2.9", false).require_version(required), Ok(()));
+        assert_eq!(
+            runtime("3.2.8", false).require_version(required),
+            Err(ZoraxyVersionError::Unsupported {
+                found: ZoraxyVersion::new(3, 2, 8),
+                required
+            })
+        );
+        assert!(runtime("unknown", false).require_version(required).is_err());
+
+        assert_eq!(runtime("3.2.8", true).require_version(required), Ok(()));
+        assert_eq!(runtime("unknown", true).require_version(required), Ok(()));
+    }
+}
@@ -0,0 +1,129 @@
+//! End-to-end tests driving the example plugins through the mock Zoraxy host
+
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::OnceLock;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode, header};
+use zoraxy_rs::host::{Capture, PluginHost};
+use zoraxy_rs::{Event, EventName};
+
+const EXAMPLES: [&str; 3] = [
+    "static_capture_example",
+    "dynamic_capture_example",
+    "event_subscriber_example",
+];
+
+/// Build the example plugins once, and return the path to the binary of `name`
+fn example(name: &str) -> PathBuf {
+    static BUILT: OnceLock<()> = OnceLock::new();
+    BUILT.get_or_init(|| {
+        let mut cargo = Command::new(env!("CARGO"));
+        cargo
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .args(["build", "--quiet"]);
+        for example in EXAMPLES {
+            cargo.args(["--example", example]);
+        }
+        if !cfg!(debug_assertions) {
+            cargo.arg("--release");
+        }
+        let status = cargo.status().expect("failed to run cargo");
+        assert!(status.success(), "failed to build the example plugins");
+    });
+
+    // the test binary lives in target/<profile>/deps, examples in target/<profile>/examples
+    let mut path = std::env::current_exe().unwrap();
+    path.pop();
+    path.pop();
+    path.push("examples");
+    path.push(format!("{name}{}", std::env::consts::EXE_SUFFIX));
+    path
+}
+
+fn get(uri: &str) -> Request<Body> {
+    Request::get(uri).body(Body::empty()).unwrap()
+}
+
+#[tokio::test]
+async fn routes_static_captures() {
+    let host = PluginHost::launch(example("static_capture_example"))
+        .await
+        .unwrap();
+
+    let resp = host.request(get("/test_a/page?x=1")).await.unwrap();
+    assert_eq!(resp.capture, Capture::Static("/test_a".to_string()));
+    assert_eq!(resp.status, StatusCode::OK);
+    assert!(
+        resp.headers[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("text/html")
+    );
+    let body = String::from_utf8_lossy(&resp.body);
+    assert!(body.contains("captured by A handler"), "{body}");
+    assert!(body.contains("/test_a/page?x=1"), "{body}");
+
+    let resp = host.request(get("/elsewhere")).await.unwrap();
+    assert_eq!(resp.capture, Capture::NotCaptured);
+
+    assert!(host.terminate().await.unwrap().success());
+}
+
+#[tokio::test]
+async fn routes_dynamic_captures() {
+    let host = PluginHost::launch(example("dynamic_capture_example"))
+        .await
+        .unwrap();
+
+    let req = Request::post("/foobar/page")
+        .header("x-e2e-test", "forwarded")
+        .body(Body::from("hello"))
+        .unwrap();
+    let resp = host.request(req).await.unwrap();
+    assert_eq!(resp.capture, Capture::Dynamic);
+    assert_eq!(resp.status, StatusCode::OK);
+    let body = String::from_utf8_lossy(&resp.body);
+    // the sniff handler stores the remote address for the capture handler of the same request
+    assert!(body.contains("Sniffed from: 127.0.0.1:54321"), "{body}");
+    assert!(body.contains("Request Method: POST"), "{body}");
+    assert!(body.contains("\"x-e2e-test\": \"forwarded\""), "{body}");
+
+    let resp = host.request(get("/elsewhere")).await.unwrap();
+    assert_eq!(resp.capture, Capture::NotCaptured);
+
+    assert!(host.terminate().await.unwrap().success());
+}
+
+#[tokio::test]
+async fn delivers_events() {
+    let host = PluginHost::launch(example("event_subscriber_example"))
+        .await
+        .unwrap();
+
+    let event: Event = serde_json::from_value(serde_json::json!({
+        "name": "blacklistToggled",
+        "timestamp": 1_700_000_000,
+        "uuid": "e2e-event",
+        "data": { "rule_id": "e2e-rule", "enabled": true },
+    }))
+    .unwrap();
+    assert_eq!(event.name, EventName::BlacklistToggled);
+    let status = host.deliver_event(&event).await.unwrap();
+    assert_eq!(status, Some(StatusCode::OK));
+
+    // the example lists the events it received in its UI
+    let ui = reqwest::get(format!(
+        "http://127.0.0.1:{}/ui/",
+        host.configure_spec().port
+    ))
+    .await
+    .unwrap()
+    .text()
+    .await
+    .unwrap();
+    assert!(ui.contains("e2e-rule"), "{ui}");
+
+    assert!(host.terminate().await.unwrap().success());
+}